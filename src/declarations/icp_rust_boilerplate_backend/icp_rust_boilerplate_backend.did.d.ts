@@ -1,58 +1,627 @@
 import type { Principal } from '@dfinity/principal';
 import type { ActorMethod } from '@dfinity/agent';
 
-export interface Business {
+export interface Account {
+  'owner' : Principal,
+  'subaccount' : [] | [Uint8Array | number[]],
+}
+export interface AccountBalance {
+  'balance' : bigint,
+  'code' : string,
+  'kind' : AccountKind,
+  'name' : string,
+  'credit' : bigint,
+  'debit' : bigint,
+}
+export type AccountKind = { 'Asset' : null } |
+  { 'Liability' : null } |
+  { 'Revenue' : null } |
+  { 'Expense' : null } |
+  { 'Equity' : null };
+export interface AccountPayload {
+  'code' : string,
+  'kind' : AccountKind,
+  'name' : string,
+}
+export interface AuditEvent {
   'id' : bigint,
+  'method' : string,
+  'after' : [] | [string],
+  'business_id' : [] | [BusinessId],
+  'before' : [] | [string],
+  'timestamp' : bigint,
+  'entity_kind' : string,
+  'caller' : Principal,
+  'entity_id' : string,
+}
+export interface AuditFilter {
+  'to' : [] | [bigint],
+  'business_id' : [] | [BusinessId],
+  'from' : [] | [bigint],
+  'entity_kind' : [] | [string],
+  'caller' : [] | [Principal],
+  'entity_id' : [] | [string],
+}
+export interface BalanceSheet {
+  'total_equity' : bigint,
+  'liabilities' : Array<AccountBalance>,
+  'assets' : Array<AccountBalance>,
+  'total_assets' : bigint,
+  'currency' : Currency,
+  'equity' : Array<AccountBalance>,
+  'net_income' : bigint,
+  'total_liabilities' : bigint,
+}
+export interface BillingDetails {
+  'name' : string,
+  'email' : [] | [string],
+  'address' : string,
+}
+export interface Business {
+  'id' : BusinessId,
   'updated_at' : [] | [bigint],
+  'owner' : Principal,
+  'base_currency' : Currency,
   'name' : string,
   'description' : string,
   'created_at' : bigint,
   'address' : string,
 }
+export interface BusinessId { 'business' : bigint }
 export interface BusinessPayload {
+  'base_currency' : Currency,
   'name' : string,
   'description' : string,
   'address' : string,
 }
-export type Error = { 'NotFound' : { 'msg' : string } };
+export interface ChartAccount {
+  'business_id' : BusinessId,
+  'code' : string,
+  'kind' : AccountKind,
+  'name' : string,
+  'built_in' : boolean,
+}
+export interface Coupon {
+  'id' : CouponId,
+  'redemption_count' : number,
+  'max_redemptions' : [] | [number],
+  'updated_at' : [] | [bigint],
+  'business_id' : BusinessId,
+  'code' : string,
+  'created_at' : bigint,
+  'valid_until' : [] | [bigint],
+  'discount' : Discount,
+  'valid_from' : bigint,
+  'min_order_value' : [] | [Money],
+  'max_redemptions_per_customer' : [] | [number],
+}
+export interface CouponId { 'coupon' : bigint }
+export interface CouponPayload {
+  'max_redemptions' : [] | [number],
+  'business_id' : BusinessId,
+  'code' : string,
+  'valid_until' : [] | [bigint],
+  'discount' : Discount,
+  'valid_from' : bigint,
+  'min_order_value' : [] | [Money],
+  'max_redemptions_per_customer' : [] | [number],
+}
+export interface Currency { 'decimals' : number, 'code' : string }
+export interface Customer {
+  'id' : CustomerId,
+  'updated_at' : [] | [bigint],
+  'principal' : [] | [Principal],
+  'business_id' : BusinessId,
+  'name' : string,
+  'created_at' : bigint,
+  'email' : string,
+  'address' : string,
+  'phone' : string,
+}
+export interface CustomerId { 'customer' : bigint }
+export interface CustomerPayload {
+  'principal' : [] | [Principal],
+  'business_id' : BusinessId,
+  'name' : string,
+  'email' : string,
+  'address' : string,
+  'phone' : string,
+}
+export interface CustomerSummary {
+  'order_count' : bigint,
+  'last_order_at' : [] | [bigint],
+  'customer_id' : CustomerId,
+  'lifetime_spend' : Array<Money>,
+}
+export type Discount = { 'Fixed' : Money } |
+  { 'Percentage' : number };
+export interface DiscountLine {
+  'code' : string,
+  'coupon_id' : CouponId,
+  'discount' : Discount,
+  'amount' : Money,
+}
+export interface EntryId { 'entry' : bigint }
+export type EntrySource = { 'Overpayment' : OrderId } |
+  { 'Refund' : { 'return_id' : [] | [ReturnId], 'order_id' : OrderId } } |
+  { 'Sale' : OrderId } |
+  { 'Cancellation' : OrderId } |
+  { 'Payment' : OrderId } |
+  { 'Manual' : null };
+export type Error = { 'Internal' : { 'msg' : string } } |
+  { 'InvalidInput' : { 'msg' : string, 'fields' : Array<FieldError> } } |
+  { 'PaymentFailed' : { 'msg' : string } } |
+  { 'NotFound' : { 'msg' : string } } |
+  { 'InsufficientStock' : { 'msg' : string, 'items' : Array<StockShortage> } } |
+  { 'Unauthorized' : { 'msg' : string } } |
+  { 'CurrencyMismatch' : { 'msg' : string } } |
+  { 'LimitExceeded' : { 'msg' : string } } |
+  { 'InvalidState' : { 'msg' : string } } |
+  { 'Conflict' : { 'msg' : string } };
+export interface ExchangeRate {
+  'updated_at' : bigint,
+  'updated_by' : Principal,
+  'base' : Currency,
+  'rate' : bigint,
+  'quote' : Currency,
+}
+export interface FieldError { 'msg' : string, 'field' : string }
+export type Granularity = { 'Day' : null } |
+  { 'Week' : null } |
+  { 'Month' : null };
+export interface Invoice {
+  'id' : InvoiceId,
+  'status' : InvoiceStatus,
+  'total' : Money,
+  'issued_at' : [] | [bigint],
+  'payment_terms_days' : number,
+  'business_id' : BusinessId,
+  'created_at' : bigint,
+  'seller' : BillingDetails,
+  'voided_at' : [] | [bigint],
+  'due_at' : [] | [bigint],
+  'number' : [] | [bigint],
+  'discount' : [] | [Money],
+  'paid_at' : [] | [bigint],
+  'buyer' : [] | [BillingDetails],
+  'order_id' : OrderId,
+  'tax_total' : Money,
+  'subtotal' : Money,
+}
+export interface InvoiceId { 'invoice' : bigint }
+export type InvoiceStatus = { 'Paid' : null } |
+  { 'Void' : null } |
+  { 'Draft' : null } |
+  { 'Overdue' : null } |
+  { 'Issued' : null };
+export interface JournalEntry {
+  'id' : EntryId,
+  'source' : EntrySource,
+  'business_id' : BusinessId,
+  'date' : bigint,
+  'memo' : string,
+  'lines' : Array<JournalLine>,
+  'posted_at' : bigint,
+  'posted_by' : Principal,
+  'currency' : Currency,
+}
+export interface JournalEntryPayload {
+  'business_id' : BusinessId,
+  'date' : [] | [bigint],
+  'memo' : string,
+  'lines' : Array<JournalLine>,
+  'currency' : Currency,
+}
+export interface JournalLine {
+  'credit' : bigint,
+  'account' : string,
+  'debit' : bigint,
+}
+export interface LedgerConfig {
+  'updated_at' : bigint,
+  'updated_by' : Principal,
+  'ledger' : Principal,
+  'currency' : Currency,
+}
+export interface Member {
+  'principal' : Principal,
+  'business_id' : BusinessId,
+  'role' : Role,
+  'granted_at' : bigint,
+}
+export interface MigrationState {
+  'schema_version' : number,
+  'rewrite' : [] | [RewriteCursor],
+}
+export interface Money {
+  'decimals' : number,
+  'currency' : string,
+  'amount' : bigint,
+}
 export interface Order {
-  'id' : bigint,
+  'id' : OrderId,
+  'status' : OrderStatus,
   'updated_at' : [] | [bigint],
-  'total_price' : bigint,
+  'tax_lines' : Array<TaxLine>,
+  'business_id' : BusinessId,
+  'total_price' : Money,
   'created_at' : bigint,
-  'products' : Array<Product>,
+  'lines' : Array<OrderLine>,
+  'customer_id' : [] | [CustomerId],
+  'status_history' : Array<StatusChange>,
+  'discount' : [] | [DiscountLine],
+  'exchange_rate' : [] | [ExchangeRate],
+  'payment' : [] | [Payment],
+  'subtotal' : Money,
+}
+export interface OrderId { 'order' : bigint }
+export interface OrderLine {
+  'product_id' : ProductId,
+  'unit_price' : Money,
+  'quantity' : number,
+  'line_total' : Money,
+}
+export interface OrderLinePayload {
+  'product_id' : ProductId,
+  'quantity' : number,
+}
+export interface OrderPayload {
+  'business_id' : BusinessId,
+  'lines' : Array<OrderLinePayload>,
+  'customer_id' : [] | [CustomerId],
+  'currency' : [] | [string],
+  'coupon_code' : [] | [string],
+}
+export type OrderStatus = { 'Refunded' : null } |
+  { 'Paid' : null } |
+  { 'Delivered' : null } |
+  { 'Confirmed' : null } |
+  { 'Cancelled' : null } |
+  { 'Shipped' : null } |
+  { 'PartiallyRefunded' : null } |
+  { 'Pending' : null };
+export interface Page {
+  'next_cursor' : [] | [bigint],
+  'items' : Array<AuditEvent>,
+}
+export interface Page_1 {
+  'next_cursor' : [] | [BusinessId],
+  'items' : Array<Business>,
+}
+export interface Page_2 {
+  'next_cursor' : [] | [CustomerId],
+  'items' : Array<Customer>,
+}
+export interface Page_3 {
+  'next_cursor' : [] | [InvoiceId],
+  'items' : Array<Invoice>,
+}
+export interface Page_4 {
+  'next_cursor' : [] | [EntryId],
+  'items' : Array<JournalEntry>,
+}
+export interface Page_5 {
+  'next_cursor' : [] | [OrderId],
+  'items' : Array<Order>,
+}
+export interface Page_6 {
+  'next_cursor' : [] | [ProductId],
+  'items' : Array<Product>,
 }
-export interface OrderPayload { 'product_ids' : BigUint64Array | bigint[] }
+export interface Payment {
+  'to' : Account,
+  'method' : PaymentMethod,
+  'block_index' : bigint,
+  'from' : Account,
+  'overpaid' : Money,
+  'ledger' : Principal,
+  'paid_at' : bigint,
+  'amount' : Money,
+}
+export type PaymentMethod = { 'Deposit' : null } |
+  { 'Approval' : null };
 export interface Product {
-  'id' : bigint,
+  'id' : ProductId,
   'updated_at' : [] | [bigint],
+  'business_id' : BusinessId,
   'name' : string,
   'description' : string,
   'created_at' : bigint,
-  'price' : bigint,
+  'prices' : Array<Money>,
+  'tax_category' : [] | [string],
+  'stock_quantity' : bigint,
+  'price' : Money,
 }
+export interface ProductId { 'product' : bigint }
 export interface ProductPayload {
+  'business_id' : BusinessId,
   'name' : string,
   'description' : string,
-  'price' : bigint,
+  'prices' : Array<Money>,
+  'tax_category' : [] | [string],
+  'price' : Money,
+}
+export interface ProductSales {
+  'revenue' : Money,
+  'product_id' : ProductId,
+  'units' : bigint,
+}
+export interface ProfitAndLoss {
+  'revenue' : Array<AccountBalance>,
+  'expenses' : Array<AccountBalance>,
+  'currency' : Currency,
+  'total_revenue' : bigint,
+  'total_expenses' : bigint,
+  'net_income' : bigint,
 }
-export type Result = { 'Ok' : Order } |
+export type RecordKind = { 'OrderLine' : null } |
+  { 'Member' : null } |
+  { 'PrincipalRedemption' : null } |
+  { 'Business' : null } |
+  { 'OrderSales' : null } |
+  { 'Product' : null } |
+  { 'Order' : null } |
+  { 'LegacyOrder' : null } |
+  { 'OrderBuyer' : null };
+export interface Refund {
+  'to' : Account,
+  'block_index' : bigint,
+  'ledger' : Principal,
+  'refunded_at' : bigint,
+  'amount' : Money,
+}
+export type Result = { 'Ok' : ChartAccount } |
   { 'Err' : Error };
 export type Result_1 = { 'Ok' : Business } |
   { 'Err' : Error };
-export type Result_2 = { 'Ok' : Product } |
+export type Result_10 = { 'Ok' : Account } |
+  { 'Err' : Error };
+export type Result_11 = { 'Ok' : CustomerSummary } |
+  { 'Err' : Error };
+export type Result_12 = { 'Ok' : JournalEntry } |
+  { 'Err' : Error };
+export type Result_13 = { 'Ok' : MigrationState } |
+  { 'Err' : Error };
+export type Result_14 = { 'Ok' : Array<ProfitAndLoss> } |
+  { 'Err' : Error };
+export type Result_15 = { 'Ok' : SalesReport } |
+  { 'Err' : Error };
+export type Result_16 = { 'Ok' : Array<TrialBalance> } |
+  { 'Err' : Error };
+export type Result_17 = { 'Ok' : Member } |
+  { 'Err' : Error };
+export type Result_18 = { 'Ok' : Array<ChartAccount> } |
+  { 'Err' : Error };
+export type Result_19 = { 'Ok' : Page_1 } |
+  { 'Err' : Error };
+export type Result_2 = { 'Ok' : Customer } |
+  { 'Err' : Error };
+export type Result_20 = { 'Ok' : Array<Coupon> } |
+  { 'Err' : Error };
+export type Result_21 = { 'Ok' : Page_2 } |
+  { 'Err' : Error };
+export type Result_22 = { 'Ok' : Array<ExchangeRate> } |
+  { 'Err' : Error };
+export type Result_23 = { 'Ok' : Page_3 } |
+  { 'Err' : Error };
+export type Result_24 = { 'Ok' : Page_4 } |
+  { 'Err' : Error };
+export type Result_25 = { 'Ok' : Array<LedgerConfig> } |
+  { 'Err' : Error };
+export type Result_26 = { 'Ok' : Array<Member> } |
+  { 'Err' : Error };
+export type Result_27 = { 'Ok' : Page_5 } |
+  { 'Err' : Error };
+export type Result_28 = { 'Ok' : Page_6 } |
+  { 'Err' : Error };
+export type Result_29 = { 'Ok' : Array<ReturnAuthorization> } |
   { 'Err' : Error };
+export type Result_3 = { 'Ok' : Product } |
+  { 'Err' : Error };
+export type Result_30 = { 'Ok' : Array<TaxRate> } |
+  { 'Err' : Error };
+export type Result_31 = { 'Ok' : ExchangeRate } |
+  { 'Err' : Error };
+export type Result_32 = { 'Ok' : LedgerConfig } |
+  { 'Err' : Error };
+export type Result_33 = { 'Ok' : Array<Business> } |
+  { 'Err' : Error };
+export type Result_34 = { 'Ok' : Array<Product> } |
+  { 'Err' : Error };
+export type Result_4 = { 'Ok' : ReturnAuthorization } |
+  { 'Err' : Error };
+export type Result_5 = { 'Ok' : Order } |
+  { 'Err' : Error };
+export type Result_6 = { 'Ok' : Coupon } |
+  { 'Err' : Error };
+export type Result_7 = { 'Ok' : Invoice } |
+  { 'Err' : Error };
+export type Result_8 = { 'Ok' : Page } |
+  { 'Err' : Error };
+export type Result_9 = { 'Ok' : Array<BalanceSheet> } |
+  { 'Err' : Error };
+export interface ReturnAuthorization {
+  'id' : ReturnId,
+  'status' : ReturnStatus,
+  'business_id' : BusinessId,
+  'refund_to' : [] | [Account],
+  'requested_at' : bigint,
+  'requested_by' : Principal,
+  'lines' : Array<ReturnLine>,
+  'refund_amount' : Money,
+  'restocked' : boolean,
+  'order_id' : OrderId,
+  'decided_at' : [] | [bigint],
+  'decided_by' : [] | [Principal],
+  'refund' : [] | [Refund],
+}
+export interface ReturnId { 'rma' : bigint }
+export interface ReturnLine {
+  'line_no' : number,
+  'product_id' : ProductId,
+  'quantity' : number,
+  'reason' : string,
+}
+export interface ReturnLinePayload {
+  'line_no' : number,
+  'quantity' : number,
+  'reason' : string,
+}
+export interface ReturnPayload {
+  'refund_to' : [] | [Account],
+  'lines' : Array<ReturnLinePayload>,
+  'refund_amount' : [] | [Money],
+  'order_id' : OrderId,
+}
+export type ReturnStatus = { 'Approved' : null } |
+  { 'Rejected' : null } |
+  { 'Requested' : null };
+export interface RewriteCursor { 'next_key' : bigint, 'kind' : RecordKind }
+export type Role = { 'Viewer' : null } |
+  { 'Cashier' : null } |
+  { 'Owner' : null } |
+  { 'Manager' : null };
+export interface SalesPeriod {
+  'revenue' : Money,
+  'order_count' : bigint,
+  'start' : bigint,
+  'average_order_value' : Money,
+  'units' : bigint,
+}
+export interface SalesReport {
+  'periods' : Array<SalesPeriod>,
+  'top_products' : Array<ProductSales>,
+}
+export interface StatusChange {
+  'status' : OrderStatus,
+  'changed_at' : bigint,
+  'changed_by' : Principal,
+}
+export interface StockShortage {
+  'requested' : bigint,
+  'product_id' : ProductId,
+  'available' : bigint,
+}
+export interface TaxLine {
+  'tax' : Money,
+  'rate' : TaxRate,
+  'taxable_amount' : Money,
+}
+export interface TaxRate {
+  'inclusive' : boolean,
+  'name' : string,
+  'rate' : number,
+  'jurisdiction' : string,
+  'category' : string,
+}
+export interface TrialBalance {
+  'total_debit' : bigint,
+  'accounts' : Array<AccountBalance>,
+  'currency' : Currency,
+  'total_credit' : bigint,
+}
 export interface _SERVICE {
-  'add_business' : ActorMethod<[BusinessPayload], [] | [Business]>,
-  'add_product' : ActorMethod<[ProductPayload], [] | [Product]>,
-  'create_order' : ActorMethod<[OrderPayload], Result>,
-  'delete_business' : ActorMethod<[bigint], Result_1>,
-  'delete_order' : ActorMethod<[bigint], Result>,
-  'delete_product' : ActorMethod<[bigint], Result_2>,
-  'get_business' : ActorMethod<[bigint], Result_1>,
-  'get_order' : ActorMethod<[bigint], Result>,
-  'get_product' : ActorMethod<[bigint], Result_2>,
-  'update_business' : ActorMethod<[bigint, BusinessPayload], Result_1>,
-  'update_order' : ActorMethod<[bigint, OrderPayload], Result>,
-  'update_product' : ActorMethod<[bigint, ProductPayload], Result_2>,
+  'add_account' : ActorMethod<[BusinessId, AccountPayload], Result>,
+  'add_business' : ActorMethod<[BusinessPayload], Result_1>,
+  'add_customer' : ActorMethod<[CustomerPayload], Result_2>,
+  'add_product' : ActorMethod<[ProductPayload], Result_3>,
+  'adjust_stock' : ActorMethod<[ProductId, bigint], Result_3>,
+  'approve_return' : ActorMethod<[ReturnId, boolean], Result_4>,
+  'cancel_order' : ActorMethod<[OrderId], Result_5>,
+  'check_order_payment' : ActorMethod<[OrderId], Result_5>,
+  'confirm_order' : ActorMethod<[OrderId], Result_5>,
+  'create_coupon' : ActorMethod<[CouponPayload], Result_6>,
+  'create_invoice' : ActorMethod<[OrderId, number], Result_7>,
+  'create_order' : ActorMethod<[OrderPayload], Result_5>,
+  'delete_business' : ActorMethod<[BusinessId], Result_1>,
+  'delete_coupon' : ActorMethod<[CouponId], Result_6>,
+  'delete_customer' : ActorMethod<[CustomerId], Result_2>,
+  'delete_order' : ActorMethod<[OrderId], Result_5>,
+  'delete_product' : ActorMethod<[ProductId], Result_3>,
+  'deliver_order' : ActorMethod<[OrderId], Result_5>,
+  'get_audit_log' : ActorMethod<[AuditFilter, [] | [bigint], number], Result_8>,
+  'get_balance_sheet' : ActorMethod<
+    [BusinessId, [] | [bigint], [] | [bigint]],
+    Result_9
+  >,
+  'get_business' : ActorMethod<[BusinessId], Result_1>,
+  'get_business_account' : ActorMethod<[BusinessId], Result_10>,
+  'get_coupon' : ActorMethod<[CouponId], Result_6>,
+  'get_customer' : ActorMethod<[CustomerId], Result_2>,
+  'get_customer_summary' : ActorMethod<[CustomerId], Result_11>,
+  'get_invoice' : ActorMethod<[InvoiceId], Result_7>,
+  'get_journal_entry' : ActorMethod<[EntryId], Result_12>,
+  'get_migration_state' : ActorMethod<[], Result_13>,
+  'get_order' : ActorMethod<[OrderId], Result_5>,
+  'get_order_deposit_account' : ActorMethod<[OrderId], Result_10>,
+  'get_product' : ActorMethod<[ProductId], Result_3>,
+  'get_profit_and_loss' : ActorMethod<
+    [BusinessId, [] | [bigint], [] | [bigint]],
+    Result_14
+  >,
+  'get_return' : ActorMethod<[ReturnId], Result_4>,
+  'get_sales_report' : ActorMethod<
+    [BusinessId, [] | [bigint], [] | [bigint], Granularity],
+    Result_15
+  >,
+  'get_trial_balance' : ActorMethod<
+    [BusinessId, [] | [bigint], [] | [bigint]],
+    Result_16
+  >,
+  'grant_role' : ActorMethod<[BusinessId, Principal, Role], Result_17>,
+  'issue_invoice' : ActorMethod<[InvoiceId], Result_7>,
+  'list_accounts' : ActorMethod<[BusinessId], Result_18>,
+  'list_businesses' : ActorMethod<[[] | [BusinessId], number], Result_19>,
+  'list_coupons' : ActorMethod<[BusinessId], Result_20>,
+  'list_customers' : ActorMethod<
+    [BusinessId, [] | [CustomerId], number],
+    Result_21
+  >,
+  'list_exchange_rates' : ActorMethod<[], Result_22>,
+  'list_invoices' : ActorMethod<
+    [BusinessId, [] | [InvoiceId], number],
+    Result_23
+  >,
+  'list_journal_entries' : ActorMethod<
+    [BusinessId, [] | [bigint], [] | [bigint], [] | [EntryId], number],
+    Result_24
+  >,
+  'list_ledgers' : ActorMethod<[], Result_25>,
+  'list_members' : ActorMethod<[BusinessId], Result_26>,
+  'list_orders' : ActorMethod<[BusinessId, [] | [OrderId], number], Result_27>,
+  'list_orders_by_customer' : ActorMethod<
+    [CustomerId, [] | [OrderId], number],
+    Result_27
+  >,
+  'list_products' : ActorMethod<[[] | [ProductId], number], Result_28>,
+  'list_products_by_business' : ActorMethod<
+    [BusinessId, [] | [ProductId], number],
+    Result_28
+  >,
+  'list_returns_by_order' : ActorMethod<[OrderId], Result_29>,
+  'list_tax_rates' : ActorMethod<[BusinessId], Result_30>,
+  'mark_order_paid' : ActorMethod<[OrderId], Result_5>,
+  'migrate_records' : ActorMethod<[number], Result_13>,
+  'pay_order' : ActorMethod<[OrderId], Result_5>,
+  'post_journal_entry' : ActorMethod<[JournalEntryPayload], Result_12>,
+  'refund_order' : ActorMethod<[OrderId], Result_5>,
+  'reject_return' : ActorMethod<[ReturnId], Result_4>,
+  'remove_exchange_rate' : ActorMethod<[string, string], Result_31>,
+  'remove_ledger' : ActorMethod<[string], Result_32>,
+  'request_return' : ActorMethod<[ReturnPayload], Result_4>,
+  'restock_product' : ActorMethod<[ProductId, bigint], Result_3>,
+  'revoke_role' : ActorMethod<[BusinessId, Principal], Result_17>,
+  'search_businesses' : ActorMethod<[string, number], Result_33>,
+  'search_products' : ActorMethod<[string, number], Result_34>,
+  'set_exchange_rate' : ActorMethod<[Currency, Currency, bigint], Result_31>,
+  'set_ledger' : ActorMethod<[Currency, Principal], Result_32>,
+  'set_tax_rates' : ActorMethod<[BusinessId, Array<TaxRate>], Result_30>,
+  'ship_order' : ActorMethod<[OrderId], Result_5>,
+  'update_account' : ActorMethod<[BusinessId, string, AccountPayload], Result>,
+  'update_business' : ActorMethod<[BusinessId, BusinessPayload], Result_1>,
+  'update_coupon' : ActorMethod<[CouponId, CouponPayload], Result_6>,
+  'update_customer' : ActorMethod<[CustomerId, CustomerPayload], Result_2>,
+  'update_order' : ActorMethod<[OrderId, OrderPayload], Result_5>,
+  'update_product' : ActorMethod<[ProductId, ProductPayload], Result_3>,
+  'void_invoice' : ActorMethod<[InvoiceId], Result_7>,
 }
@@ -1,55 +1,684 @@
 export const idlFactory = ({ IDL }) => {
+  const BusinessId = IDL.Record({ 'business' : IDL.Nat64 });
+  const AccountKind = IDL.Variant({
+    'Asset' : IDL.Null,
+    'Liability' : IDL.Null,
+    'Revenue' : IDL.Null,
+    'Expense' : IDL.Null,
+    'Equity' : IDL.Null,
+  });
+  const AccountPayload = IDL.Record({
+    'code' : IDL.Text,
+    'kind' : AccountKind,
+    'name' : IDL.Text,
+  });
+  const ChartAccount = IDL.Record({
+    'business_id' : BusinessId,
+    'code' : IDL.Text,
+    'kind' : AccountKind,
+    'name' : IDL.Text,
+    'built_in' : IDL.Bool,
+  });
+  const FieldError = IDL.Record({ 'msg' : IDL.Text, 'field' : IDL.Text });
+  const ProductId = IDL.Record({ 'product' : IDL.Nat64 });
+  const StockShortage = IDL.Record({
+    'requested' : IDL.Nat64,
+    'product_id' : ProductId,
+    'available' : IDL.Nat64,
+  });
+  const Error = IDL.Variant({
+    'Internal' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidInput' : IDL.Record({
+      'msg' : IDL.Text,
+      'fields' : IDL.Vec(FieldError),
+    }),
+    'PaymentFailed' : IDL.Record({ 'msg' : IDL.Text }),
+    'NotFound' : IDL.Record({ 'msg' : IDL.Text }),
+    'InsufficientStock' : IDL.Record({
+      'msg' : IDL.Text,
+      'items' : IDL.Vec(StockShortage),
+    }),
+    'Unauthorized' : IDL.Record({ 'msg' : IDL.Text }),
+    'CurrencyMismatch' : IDL.Record({ 'msg' : IDL.Text }),
+    'LimitExceeded' : IDL.Record({ 'msg' : IDL.Text }),
+    'InvalidState' : IDL.Record({ 'msg' : IDL.Text }),
+    'Conflict' : IDL.Record({ 'msg' : IDL.Text }),
+  });
+  const Result = IDL.Variant({ 'Ok' : ChartAccount, 'Err' : Error });
+  const Currency = IDL.Record({ 'decimals' : IDL.Nat8, 'code' : IDL.Text });
   const BusinessPayload = IDL.Record({
+    'base_currency' : Currency,
     'name' : IDL.Text,
     'description' : IDL.Text,
     'address' : IDL.Text,
   });
   const Business = IDL.Record({
-    'id' : IDL.Nat64,
+    'id' : BusinessId,
     'updated_at' : IDL.Opt(IDL.Nat64),
+    'owner' : IDL.Principal,
+    'base_currency' : Currency,
     'name' : IDL.Text,
     'description' : IDL.Text,
     'created_at' : IDL.Nat64,
     'address' : IDL.Text,
   });
+  const Result_1 = IDL.Variant({ 'Ok' : Business, 'Err' : Error });
+  const CustomerPayload = IDL.Record({
+    'principal' : IDL.Opt(IDL.Principal),
+    'business_id' : BusinessId,
+    'name' : IDL.Text,
+    'email' : IDL.Text,
+    'address' : IDL.Text,
+    'phone' : IDL.Text,
+  });
+  const CustomerId = IDL.Record({ 'customer' : IDL.Nat64 });
+  const Customer = IDL.Record({
+    'id' : CustomerId,
+    'updated_at' : IDL.Opt(IDL.Nat64),
+    'principal' : IDL.Opt(IDL.Principal),
+    'business_id' : BusinessId,
+    'name' : IDL.Text,
+    'created_at' : IDL.Nat64,
+    'email' : IDL.Text,
+    'address' : IDL.Text,
+    'phone' : IDL.Text,
+  });
+  const Result_2 = IDL.Variant({ 'Ok' : Customer, 'Err' : Error });
+  const Money = IDL.Record({
+    'decimals' : IDL.Nat8,
+    'currency' : IDL.Text,
+    'amount' : IDL.Nat64,
+  });
   const ProductPayload = IDL.Record({
+    'business_id' : BusinessId,
     'name' : IDL.Text,
     'description' : IDL.Text,
-    'price' : IDL.Nat64,
+    'prices' : IDL.Vec(Money),
+    'tax_category' : IDL.Opt(IDL.Text),
+    'price' : Money,
   });
   const Product = IDL.Record({
-    'id' : IDL.Nat64,
+    'id' : ProductId,
     'updated_at' : IDL.Opt(IDL.Nat64),
+    'business_id' : BusinessId,
     'name' : IDL.Text,
     'description' : IDL.Text,
     'created_at' : IDL.Nat64,
-    'price' : IDL.Nat64,
+    'prices' : IDL.Vec(Money),
+    'tax_category' : IDL.Opt(IDL.Text),
+    'stock_quantity' : IDL.Nat64,
+    'price' : Money,
+  });
+  const Result_3 = IDL.Variant({ 'Ok' : Product, 'Err' : Error });
+  const ReturnId = IDL.Record({ 'rma' : IDL.Nat64 });
+  const ReturnStatus = IDL.Variant({
+    'Approved' : IDL.Null,
+    'Rejected' : IDL.Null,
+    'Requested' : IDL.Null,
+  });
+  const Account = IDL.Record({
+    'owner' : IDL.Principal,
+    'subaccount' : IDL.Opt(IDL.Vec(IDL.Nat8)),
+  });
+  const ReturnLine = IDL.Record({
+    'line_no' : IDL.Nat32,
+    'product_id' : ProductId,
+    'quantity' : IDL.Nat32,
+    'reason' : IDL.Text,
+  });
+  const OrderId = IDL.Record({ 'order' : IDL.Nat64 });
+  const Refund = IDL.Record({
+    'to' : Account,
+    'block_index' : IDL.Nat,
+    'ledger' : IDL.Principal,
+    'refunded_at' : IDL.Nat64,
+    'amount' : Money,
+  });
+  const ReturnAuthorization = IDL.Record({
+    'id' : ReturnId,
+    'status' : ReturnStatus,
+    'business_id' : BusinessId,
+    'refund_to' : IDL.Opt(Account),
+    'requested_at' : IDL.Nat64,
+    'requested_by' : IDL.Principal,
+    'lines' : IDL.Vec(ReturnLine),
+    'refund_amount' : Money,
+    'restocked' : IDL.Bool,
+    'order_id' : OrderId,
+    'decided_at' : IDL.Opt(IDL.Nat64),
+    'decided_by' : IDL.Opt(IDL.Principal),
+    'refund' : IDL.Opt(Refund),
+  });
+  const Result_4 = IDL.Variant({ 'Ok' : ReturnAuthorization, 'Err' : Error });
+  const OrderStatus = IDL.Variant({
+    'Refunded' : IDL.Null,
+    'Paid' : IDL.Null,
+    'Delivered' : IDL.Null,
+    'Confirmed' : IDL.Null,
+    'Cancelled' : IDL.Null,
+    'Shipped' : IDL.Null,
+    'PartiallyRefunded' : IDL.Null,
+    'Pending' : IDL.Null,
+  });
+  const TaxRate = IDL.Record({
+    'inclusive' : IDL.Bool,
+    'name' : IDL.Text,
+    'rate' : IDL.Nat32,
+    'jurisdiction' : IDL.Text,
+    'category' : IDL.Text,
+  });
+  const TaxLine = IDL.Record({
+    'tax' : Money,
+    'rate' : TaxRate,
+    'taxable_amount' : Money,
+  });
+  const OrderLine = IDL.Record({
+    'product_id' : ProductId,
+    'unit_price' : Money,
+    'quantity' : IDL.Nat32,
+    'line_total' : Money,
+  });
+  const StatusChange = IDL.Record({
+    'status' : OrderStatus,
+    'changed_at' : IDL.Nat64,
+    'changed_by' : IDL.Principal,
+  });
+  const CouponId = IDL.Record({ 'coupon' : IDL.Nat64 });
+  const Discount = IDL.Variant({ 'Fixed' : Money, 'Percentage' : IDL.Nat32 });
+  const DiscountLine = IDL.Record({
+    'code' : IDL.Text,
+    'coupon_id' : CouponId,
+    'discount' : Discount,
+    'amount' : Money,
+  });
+  const ExchangeRate = IDL.Record({
+    'updated_at' : IDL.Nat64,
+    'updated_by' : IDL.Principal,
+    'base' : Currency,
+    'rate' : IDL.Nat64,
+    'quote' : Currency,
+  });
+  const PaymentMethod = IDL.Variant({
+    'Deposit' : IDL.Null,
+    'Approval' : IDL.Null,
+  });
+  const Payment = IDL.Record({
+    'to' : Account,
+    'method' : PaymentMethod,
+    'block_index' : IDL.Nat,
+    'from' : Account,
+    'overpaid' : Money,
+    'ledger' : IDL.Principal,
+    'paid_at' : IDL.Nat64,
+    'amount' : Money,
   });
-  const OrderPayload = IDL.Record({ 'product_ids' : IDL.Vec(IDL.Nat64) });
   const Order = IDL.Record({
-    'id' : IDL.Nat64,
+    'id' : OrderId,
+    'status' : OrderStatus,
     'updated_at' : IDL.Opt(IDL.Nat64),
-    'total_price' : IDL.Nat64,
+    'tax_lines' : IDL.Vec(TaxLine),
+    'business_id' : BusinessId,
+    'total_price' : Money,
     'created_at' : IDL.Nat64,
-    'products' : IDL.Vec(Product),
+    'lines' : IDL.Vec(OrderLine),
+    'customer_id' : IDL.Opt(CustomerId),
+    'status_history' : IDL.Vec(StatusChange),
+    'discount' : IDL.Opt(DiscountLine),
+    'exchange_rate' : IDL.Opt(ExchangeRate),
+    'payment' : IDL.Opt(Payment),
+    'subtotal' : Money,
   });
-  const Error = IDL.Variant({ 'NotFound' : IDL.Record({ 'msg' : IDL.Text }) });
-  const Result = IDL.Variant({ 'Ok' : Order, 'Err' : Error });
-  const Result_1 = IDL.Variant({ 'Ok' : Business, 'Err' : Error });
-  const Result_2 = IDL.Variant({ 'Ok' : Product, 'Err' : Error });
+  const Result_5 = IDL.Variant({ 'Ok' : Order, 'Err' : Error });
+  const CouponPayload = IDL.Record({
+    'max_redemptions' : IDL.Opt(IDL.Nat32),
+    'business_id' : BusinessId,
+    'code' : IDL.Text,
+    'valid_until' : IDL.Opt(IDL.Nat64),
+    'discount' : Discount,
+    'valid_from' : IDL.Nat64,
+    'min_order_value' : IDL.Opt(Money),
+    'max_redemptions_per_customer' : IDL.Opt(IDL.Nat32),
+  });
+  const Coupon = IDL.Record({
+    'id' : CouponId,
+    'redemption_count' : IDL.Nat32,
+    'max_redemptions' : IDL.Opt(IDL.Nat32),
+    'updated_at' : IDL.Opt(IDL.Nat64),
+    'business_id' : BusinessId,
+    'code' : IDL.Text,
+    'created_at' : IDL.Nat64,
+    'valid_until' : IDL.Opt(IDL.Nat64),
+    'discount' : Discount,
+    'valid_from' : IDL.Nat64,
+    'min_order_value' : IDL.Opt(Money),
+    'max_redemptions_per_customer' : IDL.Opt(IDL.Nat32),
+  });
+  const Result_6 = IDL.Variant({ 'Ok' : Coupon, 'Err' : Error });
+  const InvoiceId = IDL.Record({ 'invoice' : IDL.Nat64 });
+  const InvoiceStatus = IDL.Variant({
+    'Paid' : IDL.Null,
+    'Void' : IDL.Null,
+    'Draft' : IDL.Null,
+    'Overdue' : IDL.Null,
+    'Issued' : IDL.Null,
+  });
+  const BillingDetails = IDL.Record({
+    'name' : IDL.Text,
+    'email' : IDL.Opt(IDL.Text),
+    'address' : IDL.Text,
+  });
+  const Invoice = IDL.Record({
+    'id' : InvoiceId,
+    'status' : InvoiceStatus,
+    'total' : Money,
+    'issued_at' : IDL.Opt(IDL.Nat64),
+    'payment_terms_days' : IDL.Nat32,
+    'business_id' : BusinessId,
+    'created_at' : IDL.Nat64,
+    'seller' : BillingDetails,
+    'voided_at' : IDL.Opt(IDL.Nat64),
+    'due_at' : IDL.Opt(IDL.Nat64),
+    'number' : IDL.Opt(IDL.Nat64),
+    'discount' : IDL.Opt(Money),
+    'paid_at' : IDL.Opt(IDL.Nat64),
+    'buyer' : IDL.Opt(BillingDetails),
+    'order_id' : OrderId,
+    'tax_total' : Money,
+    'subtotal' : Money,
+  });
+  const Result_7 = IDL.Variant({ 'Ok' : Invoice, 'Err' : Error });
+  const OrderLinePayload = IDL.Record({
+    'product_id' : ProductId,
+    'quantity' : IDL.Nat32,
+  });
+  const OrderPayload = IDL.Record({
+    'business_id' : BusinessId,
+    'lines' : IDL.Vec(OrderLinePayload),
+    'customer_id' : IDL.Opt(CustomerId),
+    'currency' : IDL.Opt(IDL.Text),
+    'coupon_code' : IDL.Opt(IDL.Text),
+  });
+  const AuditFilter = IDL.Record({
+    'to' : IDL.Opt(IDL.Nat64),
+    'business_id' : IDL.Opt(BusinessId),
+    'from' : IDL.Opt(IDL.Nat64),
+    'entity_kind' : IDL.Opt(IDL.Text),
+    'caller' : IDL.Opt(IDL.Principal),
+    'entity_id' : IDL.Opt(IDL.Text),
+  });
+  const AuditEvent = IDL.Record({
+    'id' : IDL.Nat64,
+    'method' : IDL.Text,
+    'after' : IDL.Opt(IDL.Text),
+    'business_id' : IDL.Opt(BusinessId),
+    'before' : IDL.Opt(IDL.Text),
+    'timestamp' : IDL.Nat64,
+    'entity_kind' : IDL.Text,
+    'caller' : IDL.Principal,
+    'entity_id' : IDL.Text,
+  });
+  const Page = IDL.Record({
+    'next_cursor' : IDL.Opt(IDL.Nat64),
+    'items' : IDL.Vec(AuditEvent),
+  });
+  const Result_8 = IDL.Variant({ 'Ok' : Page, 'Err' : Error });
+  const AccountBalance = IDL.Record({
+    'balance' : IDL.Int,
+    'code' : IDL.Text,
+    'kind' : AccountKind,
+    'name' : IDL.Text,
+    'credit' : IDL.Nat,
+    'debit' : IDL.Nat,
+  });
+  const BalanceSheet = IDL.Record({
+    'total_equity' : IDL.Int,
+    'liabilities' : IDL.Vec(AccountBalance),
+    'assets' : IDL.Vec(AccountBalance),
+    'total_assets' : IDL.Int,
+    'currency' : Currency,
+    'equity' : IDL.Vec(AccountBalance),
+    'net_income' : IDL.Int,
+    'total_liabilities' : IDL.Int,
+  });
+  const Result_9 = IDL.Variant({ 'Ok' : IDL.Vec(BalanceSheet), 'Err' : Error });
+  const Result_10 = IDL.Variant({ 'Ok' : Account, 'Err' : Error });
+  const CustomerSummary = IDL.Record({
+    'order_count' : IDL.Nat64,
+    'last_order_at' : IDL.Opt(IDL.Nat64),
+    'customer_id' : CustomerId,
+    'lifetime_spend' : IDL.Vec(Money),
+  });
+  const Result_11 = IDL.Variant({ 'Ok' : CustomerSummary, 'Err' : Error });
+  const EntryId = IDL.Record({ 'entry' : IDL.Nat64 });
+  const EntrySource = IDL.Variant({
+    'Overpayment' : OrderId,
+    'Refund' : IDL.Record({
+      'return_id' : IDL.Opt(ReturnId),
+      'order_id' : OrderId,
+    }),
+    'Sale' : OrderId,
+    'Cancellation' : OrderId,
+    'Payment' : OrderId,
+    'Manual' : IDL.Null,
+  });
+  const JournalLine = IDL.Record({
+    'credit' : IDL.Nat64,
+    'account' : IDL.Text,
+    'debit' : IDL.Nat64,
+  });
+  const JournalEntry = IDL.Record({
+    'id' : EntryId,
+    'source' : EntrySource,
+    'business_id' : BusinessId,
+    'date' : IDL.Nat64,
+    'memo' : IDL.Text,
+    'lines' : IDL.Vec(JournalLine),
+    'posted_at' : IDL.Nat64,
+    'posted_by' : IDL.Principal,
+    'currency' : Currency,
+  });
+  const Result_12 = IDL.Variant({ 'Ok' : JournalEntry, 'Err' : Error });
+  const RecordKind = IDL.Variant({
+    'OrderLine' : IDL.Null,
+    'Member' : IDL.Null,
+    'PrincipalRedemption' : IDL.Null,
+    'Business' : IDL.Null,
+    'OrderSales' : IDL.Null,
+    'Product' : IDL.Null,
+    'Order' : IDL.Null,
+    'LegacyOrder' : IDL.Null,
+    'OrderBuyer' : IDL.Null,
+  });
+  const RewriteCursor = IDL.Record({
+    'next_key' : IDL.Nat64,
+    'kind' : RecordKind,
+  });
+  const MigrationState = IDL.Record({
+    'schema_version' : IDL.Nat32,
+    'rewrite' : IDL.Opt(RewriteCursor),
+  });
+  const Result_13 = IDL.Variant({ 'Ok' : MigrationState, 'Err' : Error });
+  const ProfitAndLoss = IDL.Record({
+    'revenue' : IDL.Vec(AccountBalance),
+    'expenses' : IDL.Vec(AccountBalance),
+    'currency' : Currency,
+    'total_revenue' : IDL.Int,
+    'total_expenses' : IDL.Int,
+    'net_income' : IDL.Int,
+  });
+  const Result_14 = IDL.Variant({
+    'Ok' : IDL.Vec(ProfitAndLoss),
+    'Err' : Error,
+  });
+  const Granularity = IDL.Variant({
+    'Day' : IDL.Null,
+    'Week' : IDL.Null,
+    'Month' : IDL.Null,
+  });
+  const SalesPeriod = IDL.Record({
+    'revenue' : Money,
+    'order_count' : IDL.Nat64,
+    'start' : IDL.Nat64,
+    'average_order_value' : Money,
+    'units' : IDL.Nat64,
+  });
+  const ProductSales = IDL.Record({
+    'revenue' : Money,
+    'product_id' : ProductId,
+    'units' : IDL.Nat64,
+  });
+  const SalesReport = IDL.Record({
+    'periods' : IDL.Vec(SalesPeriod),
+    'top_products' : IDL.Vec(ProductSales),
+  });
+  const Result_15 = IDL.Variant({ 'Ok' : SalesReport, 'Err' : Error });
+  const TrialBalance = IDL.Record({
+    'total_debit' : IDL.Nat,
+    'accounts' : IDL.Vec(AccountBalance),
+    'currency' : Currency,
+    'total_credit' : IDL.Nat,
+  });
+  const Result_16 = IDL.Variant({
+    'Ok' : IDL.Vec(TrialBalance),
+    'Err' : Error,
+  });
+  const Role = IDL.Variant({
+    'Viewer' : IDL.Null,
+    'Cashier' : IDL.Null,
+    'Owner' : IDL.Null,
+    'Manager' : IDL.Null,
+  });
+  const Member = IDL.Record({
+    'principal' : IDL.Principal,
+    'business_id' : BusinessId,
+    'role' : Role,
+    'granted_at' : IDL.Nat64,
+  });
+  const Result_17 = IDL.Variant({ 'Ok' : Member, 'Err' : Error });
+  const Result_18 = IDL.Variant({
+    'Ok' : IDL.Vec(ChartAccount),
+    'Err' : Error,
+  });
+  const Page_1 = IDL.Record({
+    'next_cursor' : IDL.Opt(BusinessId),
+    'items' : IDL.Vec(Business),
+  });
+  const Result_19 = IDL.Variant({ 'Ok' : Page_1, 'Err' : Error });
+  const Result_20 = IDL.Variant({ 'Ok' : IDL.Vec(Coupon), 'Err' : Error });
+  const Page_2 = IDL.Record({
+    'next_cursor' : IDL.Opt(CustomerId),
+    'items' : IDL.Vec(Customer),
+  });
+  const Result_21 = IDL.Variant({ 'Ok' : Page_2, 'Err' : Error });
+  const Result_22 = IDL.Variant({
+    'Ok' : IDL.Vec(ExchangeRate),
+    'Err' : Error,
+  });
+  const Page_3 = IDL.Record({
+    'next_cursor' : IDL.Opt(InvoiceId),
+    'items' : IDL.Vec(Invoice),
+  });
+  const Result_23 = IDL.Variant({ 'Ok' : Page_3, 'Err' : Error });
+  const Page_4 = IDL.Record({
+    'next_cursor' : IDL.Opt(EntryId),
+    'items' : IDL.Vec(JournalEntry),
+  });
+  const Result_24 = IDL.Variant({ 'Ok' : Page_4, 'Err' : Error });
+  const LedgerConfig = IDL.Record({
+    'updated_at' : IDL.Nat64,
+    'updated_by' : IDL.Principal,
+    'ledger' : IDL.Principal,
+    'currency' : Currency,
+  });
+  const Result_25 = IDL.Variant({
+    'Ok' : IDL.Vec(LedgerConfig),
+    'Err' : Error,
+  });
+  const Result_26 = IDL.Variant({ 'Ok' : IDL.Vec(Member), 'Err' : Error });
+  const Page_5 = IDL.Record({
+    'next_cursor' : IDL.Opt(OrderId),
+    'items' : IDL.Vec(Order),
+  });
+  const Result_27 = IDL.Variant({ 'Ok' : Page_5, 'Err' : Error });
+  const Page_6 = IDL.Record({
+    'next_cursor' : IDL.Opt(ProductId),
+    'items' : IDL.Vec(Product),
+  });
+  const Result_28 = IDL.Variant({ 'Ok' : Page_6, 'Err' : Error });
+  const Result_29 = IDL.Variant({
+    'Ok' : IDL.Vec(ReturnAuthorization),
+    'Err' : Error,
+  });
+  const Result_30 = IDL.Variant({ 'Ok' : IDL.Vec(TaxRate), 'Err' : Error });
+  const JournalEntryPayload = IDL.Record({
+    'business_id' : BusinessId,
+    'date' : IDL.Opt(IDL.Nat64),
+    'memo' : IDL.Text,
+    'lines' : IDL.Vec(JournalLine),
+    'currency' : Currency,
+  });
+  const Result_31 = IDL.Variant({ 'Ok' : ExchangeRate, 'Err' : Error });
+  const Result_32 = IDL.Variant({ 'Ok' : LedgerConfig, 'Err' : Error });
+  const ReturnLinePayload = IDL.Record({
+    'line_no' : IDL.Nat32,
+    'quantity' : IDL.Nat32,
+    'reason' : IDL.Text,
+  });
+  const ReturnPayload = IDL.Record({
+    'refund_to' : IDL.Opt(Account),
+    'lines' : IDL.Vec(ReturnLinePayload),
+    'refund_amount' : IDL.Opt(Money),
+    'order_id' : OrderId,
+  });
+  const Result_33 = IDL.Variant({ 'Ok' : IDL.Vec(Business), 'Err' : Error });
+  const Result_34 = IDL.Variant({ 'Ok' : IDL.Vec(Product), 'Err' : Error });
   return IDL.Service({
-    'add_business' : IDL.Func([BusinessPayload], [IDL.Opt(Business)], []),
-    'add_product' : IDL.Func([ProductPayload], [IDL.Opt(Product)], []),
-    'create_order' : IDL.Func([OrderPayload], [Result], []),
-    'delete_business' : IDL.Func([IDL.Nat64], [Result_1], []),
-    'delete_order' : IDL.Func([IDL.Nat64], [Result], []),
-    'delete_product' : IDL.Func([IDL.Nat64], [Result_2], []),
-    'get_business' : IDL.Func([IDL.Nat64], [Result_1], ['query']),
-    'get_order' : IDL.Func([IDL.Nat64], [Result], ['query']),
-    'get_product' : IDL.Func([IDL.Nat64], [Result_2], ['query']),
-    'update_business' : IDL.Func([IDL.Nat64, BusinessPayload], [Result_1], []),
-    'update_order' : IDL.Func([IDL.Nat64, OrderPayload], [Result], []),
-    'update_product' : IDL.Func([IDL.Nat64, ProductPayload], [Result_2], []),
+    'add_account' : IDL.Func([BusinessId, AccountPayload], [Result], []),
+    'add_business' : IDL.Func([BusinessPayload], [Result_1], []),
+    'add_customer' : IDL.Func([CustomerPayload], [Result_2], []),
+    'add_product' : IDL.Func([ProductPayload], [Result_3], []),
+    'adjust_stock' : IDL.Func([ProductId, IDL.Int64], [Result_3], []),
+    'approve_return' : IDL.Func([ReturnId, IDL.Bool], [Result_4], []),
+    'cancel_order' : IDL.Func([OrderId], [Result_5], []),
+    'check_order_payment' : IDL.Func([OrderId], [Result_5], []),
+    'confirm_order' : IDL.Func([OrderId], [Result_5], []),
+    'create_coupon' : IDL.Func([CouponPayload], [Result_6], []),
+    'create_invoice' : IDL.Func([OrderId, IDL.Nat32], [Result_7], []),
+    'create_order' : IDL.Func([OrderPayload], [Result_5], []),
+    'delete_business' : IDL.Func([BusinessId], [Result_1], []),
+    'delete_coupon' : IDL.Func([CouponId], [Result_6], []),
+    'delete_customer' : IDL.Func([CustomerId], [Result_2], []),
+    'delete_order' : IDL.Func([OrderId], [Result_5], []),
+    'delete_product' : IDL.Func([ProductId], [Result_3], []),
+    'deliver_order' : IDL.Func([OrderId], [Result_5], []),
+    'get_audit_log' : IDL.Func(
+        [AuditFilter, IDL.Opt(IDL.Nat64), IDL.Nat32],
+        [Result_8],
+        ['query'],
+      ),
+    'get_balance_sheet' : IDL.Func(
+        [BusinessId, IDL.Opt(IDL.Nat64), IDL.Opt(IDL.Nat64)],
+        [Result_9],
+        ['query'],
+      ),
+    'get_business' : IDL.Func([BusinessId], [Result_1], ['query']),
+    'get_business_account' : IDL.Func([BusinessId], [Result_10], ['query']),
+    'get_coupon' : IDL.Func([CouponId], [Result_6], ['query']),
+    'get_customer' : IDL.Func([CustomerId], [Result_2], ['query']),
+    'get_customer_summary' : IDL.Func([CustomerId], [Result_11], ['query']),
+    'get_invoice' : IDL.Func([InvoiceId], [Result_7], ['query']),
+    'get_journal_entry' : IDL.Func([EntryId], [Result_12], ['query']),
+    'get_migration_state' : IDL.Func([], [Result_13], ['query']),
+    'get_order' : IDL.Func([OrderId], [Result_5], ['query']),
+    'get_order_deposit_account' : IDL.Func([OrderId], [Result_10], ['query']),
+    'get_product' : IDL.Func([ProductId], [Result_3], ['query']),
+    'get_profit_and_loss' : IDL.Func(
+        [BusinessId, IDL.Opt(IDL.Nat64), IDL.Opt(IDL.Nat64)],
+        [Result_14],
+        ['query'],
+      ),
+    'get_return' : IDL.Func([ReturnId], [Result_4], ['query']),
+    'get_sales_report' : IDL.Func(
+        [BusinessId, IDL.Opt(IDL.Nat64), IDL.Opt(IDL.Nat64), Granularity],
+        [Result_15],
+        ['query'],
+      ),
+    'get_trial_balance' : IDL.Func(
+        [BusinessId, IDL.Opt(IDL.Nat64), IDL.Opt(IDL.Nat64)],
+        [Result_16],
+        ['query'],
+      ),
+    'grant_role' : IDL.Func([BusinessId, IDL.Principal, Role], [Result_17], []),
+    'issue_invoice' : IDL.Func([InvoiceId], [Result_7], []),
+    'list_accounts' : IDL.Func([BusinessId], [Result_18], ['query']),
+    'list_businesses' : IDL.Func(
+        [IDL.Opt(BusinessId), IDL.Nat32],
+        [Result_19],
+        ['query'],
+      ),
+    'list_coupons' : IDL.Func([BusinessId], [Result_20], ['query']),
+    'list_customers' : IDL.Func(
+        [BusinessId, IDL.Opt(CustomerId), IDL.Nat32],
+        [Result_21],
+        ['query'],
+      ),
+    'list_exchange_rates' : IDL.Func([], [Result_22], ['query']),
+    'list_invoices' : IDL.Func(
+        [BusinessId, IDL.Opt(InvoiceId), IDL.Nat32],
+        [Result_23],
+        ['query'],
+      ),
+    'list_journal_entries' : IDL.Func(
+        [
+          BusinessId,
+          IDL.Opt(IDL.Nat64),
+          IDL.Opt(IDL.Nat64),
+          IDL.Opt(EntryId),
+          IDL.Nat32,
+        ],
+        [Result_24],
+        ['query'],
+      ),
+    'list_ledgers' : IDL.Func([], [Result_25], ['query']),
+    'list_members' : IDL.Func([BusinessId], [Result_26], ['query']),
+    'list_orders' : IDL.Func(
+        [BusinessId, IDL.Opt(OrderId), IDL.Nat32],
+        [Result_27],
+        ['query'],
+      ),
+    'list_orders_by_customer' : IDL.Func(
+        [CustomerId, IDL.Opt(OrderId), IDL.Nat32],
+        [Result_27],
+        ['query'],
+      ),
+    'list_products' : IDL.Func(
+        [IDL.Opt(ProductId), IDL.Nat32],
+        [Result_28],
+        ['query'],
+      ),
+    'list_products_by_business' : IDL.Func(
+        [BusinessId, IDL.Opt(ProductId), IDL.Nat32],
+        [Result_28],
+        ['query'],
+      ),
+    'list_returns_by_order' : IDL.Func([OrderId], [Result_29], ['query']),
+    'list_tax_rates' : IDL.Func([BusinessId], [Result_30], ['query']),
+    'mark_order_paid' : IDL.Func([OrderId], [Result_5], []),
+    'migrate_records' : IDL.Func([IDL.Nat32], [Result_13], []),
+    'pay_order' : IDL.Func([OrderId], [Result_5], []),
+    'post_journal_entry' : IDL.Func([JournalEntryPayload], [Result_12], []),
+    'refund_order' : IDL.Func([OrderId], [Result_5], []),
+    'reject_return' : IDL.Func([ReturnId], [Result_4], []),
+    'remove_exchange_rate' : IDL.Func([IDL.Text, IDL.Text], [Result_31], []),
+    'remove_ledger' : IDL.Func([IDL.Text], [Result_32], []),
+    'request_return' : IDL.Func([ReturnPayload], [Result_4], []),
+    'restock_product' : IDL.Func([ProductId, IDL.Nat64], [Result_3], []),
+    'revoke_role' : IDL.Func([BusinessId, IDL.Principal], [Result_17], []),
+    'search_businesses' : IDL.Func(
+        [IDL.Text, IDL.Nat32],
+        [Result_33],
+        ['query'],
+      ),
+    'search_products' : IDL.Func([IDL.Text, IDL.Nat32], [Result_34], ['query']),
+    'set_exchange_rate' : IDL.Func(
+        [Currency, Currency, IDL.Nat64],
+        [Result_31],
+        [],
+      ),
+    'set_ledger' : IDL.Func([Currency, IDL.Principal], [Result_32], []),
+    'set_tax_rates' : IDL.Func([BusinessId, IDL.Vec(TaxRate)], [Result_30], []),
+    'ship_order' : IDL.Func([OrderId], [Result_5], []),
+    'update_account' : IDL.Func(
+        [BusinessId, IDL.Text, AccountPayload],
+        [Result],
+        [],
+      ),
+    'update_business' : IDL.Func([BusinessId, BusinessPayload], [Result_1], []),
+    'update_coupon' : IDL.Func([CouponId, CouponPayload], [Result_6], []),
+    'update_customer' : IDL.Func([CustomerId, CustomerPayload], [Result_2], []),
+    'update_order' : IDL.Func([OrderId, OrderPayload], [Result_5], []),
+    'update_product' : IDL.Func([ProductId, ProductPayload], [Result_3], []),
+    'void_invoice' : IDL.Func([InvoiceId], [Result_7], []),
   });
 };
 export const init = ({ IDL }) => { return []; };
@@ -1,17 +1,371 @@
 #[macro_use]
 extern crate serde;
-use candid::{Decode, Encode};
-use ic_cdk::api::time;
+use candid::{CandidType, Decode, Encode, Nat, Principal};
+use ic_cdk::api::{caller, time};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{BoundedStorable, Cell, DefaultMemoryImpl, StableBTreeMap, Storable};
+use ic_stable_structures::{
+    BoundedStorable, Cell, DefaultMemoryImpl, Log, StableBTreeMap, Storable,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::thread::LocalKey;
+use std::time::Duration;
 use std::{borrow::Cow, cell::RefCell};
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 type IdCell = Cell<u64, Memory>;
+type Migration = fn(&mut MigrationState);
 
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+// upper bound on page sizes so a single list call stays well within the query instruction limit
+const MAX_PAGE_SIZE: u32 = 100;
+
+// longer tokens are truncated before they are indexed or looked up
+const MAX_TOKEN_LEN: usize = 32;
+// a query reads at most this many index entries per token, so very common words can't
+// push a search past the instruction limit
+const MAX_POSTINGS_PER_TOKEN: usize = 10_000;
+const MAX_QUERY_TOKENS: usize = 8;
+// a word in a name counts for more than the same word in a description or address
+const NAME_WEIGHT: u32 = 3;
+
+// payload limits, in characters for text
+const MAX_NAME_LEN: usize = 100;
+const MAX_DESCRIPTION_LEN: usize = 500;
+const MAX_ADDRESS_LEN: usize = 200;
+const MAX_CONTACT_LEN: usize = 100;
+const MAX_ORDER_LINES: usize = 500;
+const MAX_LINE_QUANTITY: u32 = 10_000;
+// an order line holds two amounts and has to stay within its 128 byte bound
+const MAX_CURRENCY_LEN: usize = 8;
+const MAX_DECIMALS: u8 = 18;
+// prices a product may carry besides the one in its business's base currency
+const MAX_EXTRA_PRICES: usize = 8;
+// exchange rates are fixed point numbers with this many decimal places
+const RATE_DECIMALS: u32 = 8;
+// tax rates and percentage discounts are given in parts per million, so 190_000 is 19%
+const RATE_SCALE: u64 = 1_000_000;
+const MAX_TAX_RATES: usize = 16;
+const MAX_TAX_LABEL_LEN: usize = 32;
+const MAX_COUPON_CODE_LEN: usize = 32;
+const MAX_RETURN_LINES: usize = 20;
+const MAX_REASON_LEN: usize = 100;
+const MAX_PAYMENT_TERMS_DAYS: u32 = 365;
+// an order header keeps its first status change and at most this many in all, so returns
+// can't grow it past its size limit
+const MAX_STATUS_HISTORY: usize = 10;
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+// a timer looks for overdue invoices this often, and marks at most this many per run
+const OVERDUE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const OVERDUE_BATCH_SIZE: usize = 100;
+const MAX_ACCOUNTS: usize = 200;
+// account codes are map keys, so they can't be longer than a `StorableCode`
+const MAX_ACCOUNT_CODE_LEN: usize = 32;
+const MAX_JOURNAL_LINES: usize = 20;
+// a report reads at most this many journal entries, so it stays within the query instruction limit
+const MAX_REPORT_ENTRIES: usize = 20_000;
+const TOP_PRODUCTS: usize = 10;
+// a call to `get_audit_log` reads at most this many events
+const MAX_AUDIT_SCAN: u64 = 10_000;
+// first byte of the canister's ledger subaccounts, saying what they hold
+const BUSINESS_SUBACCOUNT_TAG: u8 = 1;
+const ORDER_SUBACCOUNT_TAG: u8 = 2;
+
+// the accounts every business has, which the canister posts order entries to
+const CASH_ACCOUNT: &str = "1000";
+const RECEIVABLES_ACCOUNT: &str = "1100";
+const SALES_TAX_ACCOUNT: &str = "2000";
+const CUSTOMER_CREDIT_ACCOUNT: &str = "2100";
+const SALES_ACCOUNT: &str = "4000";
+const SALES_RETURNS_ACCOUNT: &str = "4100";
+const BUILT_IN_ACCOUNTS: &[(&str, &str, AccountKind)] = &[
+    (CASH_ACCOUNT, "Cash", AccountKind::Asset),
+    (RECEIVABLES_ACCOUNT, "Accounts receivable", AccountKind::Asset),
+    (SALES_TAX_ACCOUNT, "Sales tax payable", AccountKind::Liability),
+    (CUSTOMER_CREDIT_ACCOUNT, "Customer credit", AccountKind::Liability),
+    ("3000", "Owner's equity", AccountKind::Equity),
+    (SALES_ACCOUNT, "Sales", AccountKind::Revenue),
+    (SALES_RETURNS_ACCOUNT, "Sales returns", AccountKind::Revenue),
+];
+
+// amounts stored before they carried a currency are read as this one
+const LEGACY_CURRENCY: &str = "USD";
+const LEGACY_DECIMALS: u8 = 2;
+
+// layout version of the data in stable memory as a whole; bump it together with a new
+// entry in `MIGRATIONS`
+const SCHEMA_VERSION: u32 = 10;
+
+// each entry brings stored data from the previous schema version up to the given one.
+// `post_upgrade` runs every entry newer than the version recorded in stable memory, in order.
+const MIGRATIONS: &[(u32, Migration)] = &[
+    (1, start_record_rewrite),
+    (2, start_order_split),
+    (3, split_id_counters),
+    (4, start_record_rewrite),
+    (5, start_record_rewrite),
+    (6, start_record_rewrite),
+    (7, start_record_rewrite),
+    (8, start_record_rewrite),
+    (9, start_sales_count),
+    (10, start_buyer_linking),
+];
+
+// stored records are wrapped in an envelope of `ENVELOPE_TAG`, a layout version byte and the
+// Candid encoding of the record. Records written before envelopes existed are bare Candid,
+// which always starts with `DIDL`, and are read as layout version 0. Only the first release
+// stored records that way, and it only had businesses, products and orders.
+const ENVELOPE_TAG: u8 = b'V';
+
+trait Versioned: CandidType + serde::de::DeserializeOwned {
+    const KIND: &'static str;
+    // layout version written by this code; bump it when a field is added, removed or retyped
+    // and teach `decode_legacy` to read the previous layout
+    const VERSION: u8;
+
+    // reads a record stored with an older layout version and converts it to the current one
+    fn decode_legacy(version: u8, _bytes: &[u8]) -> Result<Self, String> {
+        Err(format!("unknown layout version {}", version))
+    }
+}
+
+fn encode_versioned<T: Versioned>(record: &T) -> Cow<'static, [u8]> {
+    let mut bytes = vec![ENVELOPE_TAG, T::VERSION];
+    match Encode!(record) {
+        Ok(payload) => bytes.extend(payload),
+        Err(e) => ic_cdk::trap(&format!("couldn't encode a {}: {}", T::KIND, e)),
+    }
+    Cow::Owned(bytes)
+}
+
+// older layouts are upgraded in memory as they are read; they are written back in the
+// current layout the next time the record is saved or by `migrate_records`
+fn decode_versioned<T: Versioned>(bytes: &[u8]) -> T {
+    let decoded = match bytes {
+        [ENVELOPE_TAG, version, payload @ ..] if *version == T::VERSION => {
+            Decode!(payload, T).map_err(|e| e.to_string())
+        }
+        [ENVELOPE_TAG, version, payload @ ..] if *version < T::VERSION => {
+            T::decode_legacy(*version, payload)
+        }
+        [ENVELOPE_TAG, version, ..] => Err(format!(
+            "layout version {} was written by a newer canister version",
+            version
+        )),
+        legacy => T::decode_legacy(0, legacy),
+    };
+    decoded.unwrap_or_else(|e| ic_cdk::trap(&format!("couldn't decode a stored {}: {}", T::KIND, e)))
+}
+
+// ids of the different entities are separate types, so one can't be passed where another is
+// expected. Each is a single-field record whose field name differs per kind, which keeps them
+// apart in the Candid interface too.
+trait EntityId: BoundedStorable + Copy + Ord {
+    fn new(raw: u64) -> Self;
+    fn raw(self) -> u64;
+}
+
+macro_rules! entity_id {
+    ($name:ident, $field:ident) => {
+        #[derive(
+            CandidType, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+        )]
+        struct $name {
+            $field: u64,
+        }
+
+        impl EntityId for $name {
+            fn new(raw: u64) -> Self {
+                $name { $field: raw }
+            }
+
+            fn raw(self) -> u64 {
+                self.$field
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.$field)
+            }
+        }
+
+        // stored exactly like a bare `u64`, so keys written before the ids were typed stay valid
+        impl Storable for $name {
+            fn to_bytes(&self) -> Cow<'_, [u8]> {
+                Cow::Owned(self.$field.to_be_bytes().to_vec())
+            }
+
+            fn from_bytes(bytes: Cow<[u8]>) -> Self {
+                $name {
+                    $field: u64::from_bytes(bytes),
+                }
+            }
+        }
+
+        impl BoundedStorable for $name {
+            const MAX_SIZE: u32 = u64::MAX_SIZE;
+            const IS_FIXED_SIZE: bool = true;
+        }
+    };
+}
+
+entity_id!(BusinessId, business);
+entity_id!(ProductId, product);
+entity_id!(OrderId, order);
+entity_id!(CouponId, coupon);
+entity_id!(CustomerId, customer);
+entity_id!(ReturnId, rma);
+entity_id!(InvoiceId, invoice);
+entity_id!(EntryId, entry);
+
+// an amount in the smallest unit of `currency`, e.g. cents, with `decimals` digits after the
+// point. Two amounts can only be combined when both the currency and the decimals match.
+#[derive(CandidType, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+struct Money {
+    amount: u64,
+    currency: String,
+    decimals: u8,
+}
+
+#[derive(CandidType, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+struct Currency {
+    code: String,
+    decimals: u8,
+}
+
+impl Currency {
+    fn legacy() -> Currency {
+        Currency {
+            code: LEGACY_CURRENCY.to_string(),
+            decimals: LEGACY_DECIMALS,
+        }
+    }
+}
+
+impl Money {
+    fn currency(&self) -> Currency {
+        Currency {
+            code: self.currency.clone(),
+            decimals: self.decimals,
+        }
+    }
+
+    fn legacy(amount: u64) -> Money {
+        Money {
+            amount,
+            currency: LEGACY_CURRENCY.to_string(),
+            decimals: LEGACY_DECIMALS,
+        }
+    }
+
+    fn same_currency(&self, other: &Money) -> bool {
+        self.currency == other.currency && self.decimals == other.decimals
+    }
+
+    fn checked_add(&self, other: &Money) -> Result<Money, Error> {
+        if !self.same_currency(other) {
+            return Err(Error::CurrencyMismatch {
+                msg: format!("can't add {} to {}", other, self),
+            });
+        }
+        let amount = self.amount.checked_add(other.amount).ok_or_else(|| Error::LimitExceeded {
+            msg: format!("adding {} to {} overflows", other, self),
+        })?;
+        Ok(Money {
+            amount,
+            ..self.clone()
+        })
+    }
+
+    fn checked_mul(&self, factor: u64) -> Result<Money, Error> {
+        let amount = self.amount.checked_mul(factor).ok_or_else(|| Error::LimitExceeded {
+            msg: format!("{} times {} overflows", self, factor),
+        })?;
+        Ok(Money {
+            amount,
+            ..self.clone()
+        })
+    }
+}
+
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scale = 10u128.pow(self.decimals as u32);
+        let amount = self.amount as u128;
+        if self.decimals == 0 {
+            write!(f, "{} {}", amount, self.currency)
+        } else {
+            write!(
+                f,
+                "{}.{:0width$} {}",
+                amount / scale,
+                amount % scale,
+                self.currency,
+                width = self.decimals as usize
+            )
+        }
+    }
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
 struct Business {
+    id: BusinessId,
+    owner: Principal,
+    name: String,
+    description: String,
+    address: String,
+    // the currency every product of the business is priced in
+    base_currency: Currency,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl Versioned for Business {
+    const KIND: &'static str = "business";
+    const VERSION: u8 = 3;
+
+    fn decode_legacy(version: u8, bytes: &[u8]) -> Result<Self, String> {
+        match version {
+            0 => Decode!(bytes, BusinessV0)
+                .map(|old| Business::from(BusinessV2::from(BusinessV1::from(old))))
+                .map_err(|e| e.to_string()),
+            1 => Decode!(bytes, BusinessV1)
+                .map(|old| Business::from(BusinessV2::from(old)))
+                .map_err(|e| e.to_string()),
+            2 => Decode!(bytes, BusinessV2).map(Business::from).map_err(|e| e.to_string()),
+            _ => Err(format!("unknown layout version {}", version)),
+        }
+    }
+}
+
+// layout version 0, as the first release stored it before businesses had an owner
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct BusinessV0 {
+    id: u64,
+    name: String,
+    description: String,
+    address: String,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+// layout version 1, from before ids were typed. Older layouts are converted one version at a
+// time, so each conversion only deals with a single change.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct BusinessV1 {
     id: u64,
+    owner: Principal,
+    name: String,
+    description: String,
+    address: String,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+// layout version 2, from before businesses had a base currency
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct BusinessV2 {
+    id: BusinessId,
+    owner: Principal,
     name: String,
     description: String,
     address: String,
@@ -19,13 +373,58 @@ struct Business {
     updated_at: Option<u64>,
 }
 
+impl From<BusinessV2> for Business {
+    fn from(old: BusinessV2) -> Business {
+        Business {
+            id: old.id,
+            owner: old.owner,
+            name: old.name,
+            description: old.description,
+            address: old.address,
+            base_currency: Currency::legacy(),
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+// nobody is known to have created these, so the anonymous principal stands in as the owner
+// until a controller hands the business over with `grant_role`
+impl From<BusinessV0> for BusinessV1 {
+    fn from(old: BusinessV0) -> BusinessV1 {
+        BusinessV1 {
+            id: old.id,
+            owner: Principal::anonymous(),
+            name: old.name,
+            description: old.description,
+            address: old.address,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+impl From<BusinessV1> for BusinessV2 {
+    fn from(old: BusinessV1) -> BusinessV2 {
+        BusinessV2 {
+            id: BusinessId::new(old.id),
+            owner: old.owner,
+            name: old.name,
+            description: old.description,
+            address: old.address,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
 impl Storable for Business {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
     }
 
     fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+        decode_versioned(bytes.as_ref())
     }
 }
 
@@ -36,345 +435,7677 @@ impl BoundedStorable for Business {
 
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
 struct Product {
-    id: u64,
+    id: ProductId,
+    business_id: BusinessId,
     name: String,
     description: String,
-    price: u64,
+    // in the base currency of the business
+    price: Money,
+    // fixed prices in other currencies; they take precedence over converting `price`
+    prices: Vec<Money>,
+    // picks the business's tax rates that apply; untaxed when none
+    tax_category: Option<String>,
+    stock_quantity: u64,
     created_at: u64,
     updated_at: Option<u64>,
 }
 
-impl Storable for Product {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+impl Versioned for Product {
+    const KIND: &'static str = "product";
+    const VERSION: u8 = 5;
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+    fn decode_legacy(version: u8, bytes: &[u8]) -> Result<Self, String> {
+        match version {
+            0 => Decode!(bytes, ProductV0)
+                .map(|old| {
+                    Product::from(ProductV4::from(ProductV3::from(ProductV2::from(
+                        ProductV1::from(old),
+                    ))))
+                })
+                .map_err(|e| e.to_string()),
+            1 => Decode!(bytes, ProductV1)
+                .map(|old| Product::from(ProductV4::from(ProductV3::from(ProductV2::from(old)))))
+                .map_err(|e| e.to_string()),
+            2 => Decode!(bytes, ProductV2)
+                .map(|old| Product::from(ProductV4::from(ProductV3::from(old))))
+                .map_err(|e| e.to_string()),
+            3 => Decode!(bytes, ProductV3)
+                .map(|old| Product::from(ProductV4::from(old)))
+                .map_err(|e| e.to_string()),
+            4 => Decode!(bytes, ProductV4).map(Product::from).map_err(|e| e.to_string()),
+            _ => Err(format!("unknown layout version {}", version)),
+        }
     }
 }
 
-impl BoundedStorable for Product {
-    const MAX_SIZE: u32 = 1024;
-    const IS_FIXED_SIZE: bool = false;
-}
-
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct Order {
-    id: u64,
-    products: Vec<Product>,
-    total_price: u64,
+// layout version 4, from before products had a tax category
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct ProductV4 {
+    id: ProductId,
+    business_id: BusinessId,
+    name: String,
+    description: String,
+    price: Money,
+    prices: Vec<Money>,
+    stock_quantity: u64,
     created_at: u64,
     updated_at: Option<u64>,
 }
 
-impl Storable for Order {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+impl From<ProductV4> for Product {
+    fn from(old: ProductV4) -> Product {
+        Product {
+            id: old.id,
+            business_id: old.business_id,
+            name: old.name,
+            description: old.description,
+            price: old.price,
+            prices: old.prices,
+            tax_category: None,
+            stock_quantity: old.stock_quantity,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
     }
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+// layout version 3, from before products had prices in other currencies
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct ProductV3 {
+    id: ProductId,
+    business_id: BusinessId,
+    name: String,
+    description: String,
+    price: Money,
+    stock_quantity: u64,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl From<ProductV3> for ProductV4 {
+    fn from(old: ProductV3) -> ProductV4 {
+        ProductV4 {
+            id: old.id,
+            business_id: old.business_id,
+            name: old.name,
+            description: old.description,
+            price: old.price,
+            prices: Vec::new(),
+            stock_quantity: old.stock_quantity,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
     }
 }
 
-impl BoundedStorable for Order {
-    const MAX_SIZE: u32 = 1024; // Adjust the maximum size as needed
-    const IS_FIXED_SIZE: bool = false;
+// layout version 0, as the first release stored it before products belonged to a business
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct ProductV0 {
+    id: u64,
+    name: String,
+    description: String,
+    price: u64,
+    created_at: u64,
+    updated_at: Option<u64>,
 }
 
-#[derive(candid::CandidType, Serialize, Deserialize, Default)]
-struct BusinessPayload {
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct ProductV1 {
+    id: u64,
+    business_id: u64,
     name: String,
     description: String,
-    address: String,
+    price: u64,
+    stock_quantity: u64,
+    created_at: u64,
+    updated_at: Option<u64>,
 }
 
-#[derive(candid::CandidType, Serialize, Deserialize, Default)]
-struct ProductPayload {
+// layout version 2, from before prices had a currency
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct ProductV2 {
+    id: ProductId,
+    business_id: BusinessId,
     name: String,
     description: String,
     price: u64,
+    stock_quantity: u64,
+    created_at: u64,
+    updated_at: Option<u64>,
 }
 
-#[derive(candid::CandidType, Serialize, Deserialize, Default)]
-struct OrderPayload {
-    product_ids: Vec<u64>,
+impl From<ProductV2> for ProductV3 {
+    fn from(old: ProductV2) -> ProductV3 {
+        ProductV3 {
+            id: old.id,
+            business_id: old.business_id,
+            name: old.name,
+            description: old.description,
+            price: Money::legacy(old.price),
+            stock_quantity: old.stock_quantity,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
 }
 
-#[derive(candid::CandidType, Deserialize, Serialize)]
-enum Error {
-    NotFound { msg: String },
+// business id 0 is never handed out, so these stay apart from every business's catalog
+impl From<ProductV0> for ProductV1 {
+    fn from(old: ProductV0) -> ProductV1 {
+        ProductV1 {
+            id: old.id,
+            business_id: 0,
+            name: old.name,
+            description: old.description,
+            price: old.price,
+            stock_quantity: 0,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
 }
 
-thread_local! {
-    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
-        MemoryManager::init(DefaultMemoryImpl::default())
-    );
+impl From<ProductV1> for ProductV2 {
+    fn from(old: ProductV1) -> ProductV2 {
+        ProductV2 {
+            id: ProductId::new(old.id),
+            business_id: BusinessId::new(old.business_id),
+            name: old.name,
+            description: old.description,
+            price: old.price,
+            stock_quantity: old.stock_quantity,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
 
-    static ID_COUNTER: RefCell<IdCell> = RefCell::new(
-        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))), 0)
-            .expect("Cannot create a counter")
-    );
+impl Storable for Product {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
 
-    static BUSINESS_STORAGE: RefCell<StableBTreeMap<u64, Business, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1)))
-    ));
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
 
-    static PRODUCT_STORAGE: RefCell<StableBTreeMap<u64, Product, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2)))
-    ));
+impl BoundedStorable for Product {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
 
-    static ORDER_STORAGE: RefCell<StableBTreeMap<u64, Order, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3)))
-    ));
+// an order as clients see it. It is stored as an `OrderHeader` plus one `OrderLine` entry per
+// line, so the number of lines isn't limited by the size of a single record.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Order {
+    id: OrderId,
+    business_id: BusinessId,
+    lines: Vec<OrderLine>,
+    // the customer the order is for, if the business knows them
+    customer_id: Option<CustomerId>,
+    discount: Option<DiscountLine>,
+    // the line totals less the discount and any tax included in them
+    subtotal: Money,
+    tax_lines: Vec<TaxLine>,
+    // the grand total: the subtotal plus every tax line
+    total_price: Money,
+    // the rate used to convert base currency prices, if any had to be converted
+    exchange_rate: Option<ExchangeRate>,
+    // set once the order has been paid on a ledger
+    payment: Option<Payment>,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
 }
 
-#[ic_cdk::query]
-fn get_business(id: u64) -> Result<Business, Error> {
-    match _get_business(&id) {
-        Some(business) => Ok(business),
-        None => Err(Error::NotFound {
-            msg: format!("a business with id={} not found", id),
-        }),
+impl Order {
+    fn header(&self) -> OrderHeader {
+        OrderHeader {
+            id: self.id,
+            business_id: self.business_id,
+            line_count: self.lines.len() as u32,
+            customer_id: self.customer_id,
+            unlinked_buyer: None,
+            discount: self.discount.clone(),
+            subtotal: self.subtotal.clone(),
+            tax_line_count: self.tax_lines.len() as u32,
+            total_price: self.total_price.clone(),
+            exchange_rate: self.exchange_rate.clone(),
+            status: self.status,
+            status_history: self.status_history.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    fn from_parts(
+        header: OrderHeader,
+        lines: Vec<OrderLine>,
+        tax_lines: Vec<TaxLine>,
+        payment: Option<Payment>,
+    ) -> Order {
+        Order {
+            id: header.id,
+            business_id: header.business_id,
+            lines,
+            customer_id: header.customer_id,
+            discount: header.discount,
+            subtotal: header.subtotal,
+            tax_lines,
+            total_price: header.total_price,
+            exchange_rate: header.exchange_rate,
+            payment,
+            status: header.status,
+            status_history: header.status_history,
+            created_at: header.created_at,
+            updated_at: header.updated_at,
+        }
     }
 }
 
-#[ic_cdk::update]
-fn add_business(business: BusinessPayload) -> Option<Business> {
-    let id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("cannot increment id counter");
-    let business = Business {
-        id,
-        name: business.name,
-        description: business.description,
-        address: business.address,
-        created_at: time(),
-        updated_at: None,
-    };
-    do_insert_business(&business);
-    Some(business)
+// tax lines are stored apart from the header like the order lines, numbered from 0 to
+// `tax_line_count`
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct OrderHeader {
+    id: OrderId,
+    business_id: BusinessId,
+    line_count: u32,
+    customer_id: Option<CustomerId>,
+    // the buyer of an order from before there were customer records, known only by principal
+    // until `migrate_records` links them to a customer
+    unlinked_buyer: Option<Principal>,
+    discount: Option<DiscountLine>,
+    subtotal: Money,
+    tax_line_count: u32,
+    total_price: Money,
+    exchange_rate: Option<ExchangeRate>,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
 }
 
-#[ic_cdk::update]
-fn update_business(id: u64, payload: BusinessPayload) -> Result<Business, Error> {
-    match BUSINESS_STORAGE.with(|service| service.borrow().get(&id)) {
-        Some(mut business) => {
-            business.name = payload.name;
-            business.description = payload.description;
-            business.address = payload.address;
-            business.updated_at = Some(time());
-            do_insert_business(&business);
-            Ok(business)
+impl Versioned for OrderHeader {
+    const KIND: &'static str = "order";
+    const VERSION: u8 = 8;
+
+    fn decode_legacy(version: u8, bytes: &[u8]) -> Result<Self, String> {
+        match version {
+            1 => Decode!(bytes, OrderHeaderV1)
+                .map(|old| {
+                    OrderHeader::from(OrderHeaderV6::from(OrderHeaderV5::from(
+                        OrderHeaderV4::from(OrderHeaderV3::from(OrderHeaderV2::from(old))),
+                    )))
+                })
+                .map_err(|e| e.to_string()),
+            2 => Decode!(bytes, OrderHeaderV2)
+                .map(|old| {
+                    OrderHeader::from(OrderHeaderV6::from(OrderHeaderV5::from(
+                        OrderHeaderV4::from(OrderHeaderV3::from(old)),
+                    )))
+                })
+                .map_err(|e| e.to_string()),
+            3 => Decode!(bytes, OrderHeaderV3)
+                .map(|old| {
+                    OrderHeader::from(OrderHeaderV6::from(OrderHeaderV5::from(
+                        OrderHeaderV4::from(old),
+                    )))
+                })
+                .map_err(|e| e.to_string()),
+            4 => Decode!(bytes, OrderHeaderV4)
+                .map(|old| OrderHeader::from(OrderHeaderV6::from(OrderHeaderV5::from(old))))
+                .map_err(|e| e.to_string()),
+            5 => Decode!(bytes, OrderHeaderV5)
+                .map(|old| OrderHeader::from(OrderHeaderV6::from(old)))
+                .map_err(|e| e.to_string()),
+            6 => Decode!(bytes, OrderHeaderV6)
+                .map(OrderHeader::from)
+                .map_err(|e| e.to_string()),
+            7 => Decode!(bytes, OrderHeaderV7)
+                .map(OrderHeader::from)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!("unknown layout version {}", version)),
         }
-        None => Err(Error::NotFound {
-            msg: format!(
-                "couldn't update a business with id={}. business not found",
-                id
-            ),
-        }),
     }
 }
 
-fn do_insert_business(business: &Business) {
-    BUSINESS_STORAGE
-        .with(|service| service.borrow_mut().insert(business.id, business.clone()));
+// layout version 6, when an order named its buyer by principal rather than by customer record
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct OrderHeaderV6 {
+    id: OrderId,
+    business_id: BusinessId,
+    line_count: u32,
+    customer: Option<Principal>,
+    discount: Option<DiscountLine>,
+    subtotal: Money,
+    tax_line_count: u32,
+    total_price: Money,
+    exchange_rate: Option<ExchangeRate>,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
 }
 
-#[ic_cdk::update]
-fn delete_business(id: u64) -> Result<Business, Error> {
-    match BUSINESS_STORAGE.with(|service| service.borrow_mut().remove(&id)) {
-        Some(business) => Ok(business),
-        None => Err(Error::NotFound {
-            msg: format!(
-                "couldn't delete a business with id={}. business not found.",
-                id
-            ),
-        }),
+impl From<OrderHeaderV6> for OrderHeader {
+    fn from(old: OrderHeaderV6) -> OrderHeader {
+        OrderHeader {
+            id: old.id,
+            business_id: old.business_id,
+            line_count: old.line_count,
+            customer_id: None,
+            unlinked_buyer: old.customer,
+            discount: old.discount,
+            subtotal: old.subtotal,
+            tax_line_count: old.tax_line_count,
+            total_price: old.total_price,
+            exchange_rate: old.exchange_rate,
+            status: old.status,
+            status_history: old.status_history,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
     }
 }
 
-#[ic_cdk::query]
-fn get_product(id: u64) -> Result<Product, Error> {
-    match _get_product(&id) {
-        Some(product) => Ok(product),
-        None => Err(Error::NotFound {
-            msg: format!("a product with id={} not found", id),
-        }),
+// layout version 7, before orders could keep a buyer that isn't linked to a customer yet
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct OrderHeaderV7 {
+    id: OrderId,
+    business_id: BusinessId,
+    line_count: u32,
+    customer_id: Option<CustomerId>,
+    discount: Option<DiscountLine>,
+    subtotal: Money,
+    tax_line_count: u32,
+    total_price: Money,
+    exchange_rate: Option<ExchangeRate>,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl From<OrderHeaderV7> for OrderHeader {
+    fn from(old: OrderHeaderV7) -> OrderHeader {
+        OrderHeader {
+            id: old.id,
+            business_id: old.business_id,
+            line_count: old.line_count,
+            customer_id: old.customer_id,
+            unlinked_buyer: None,
+            discount: old.discount,
+            subtotal: old.subtotal,
+            tax_line_count: old.tax_line_count,
+            total_price: old.total_price,
+            exchange_rate: old.exchange_rate,
+            status: old.status,
+            status_history: old.status_history,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+// layout version 5, from before orders had a customer and a discount
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct OrderHeaderV5 {
+    id: OrderId,
+    business_id: BusinessId,
+    line_count: u32,
+    subtotal: Money,
+    tax_line_count: u32,
+    total_price: Money,
+    exchange_rate: Option<ExchangeRate>,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl From<OrderHeaderV5> for OrderHeaderV6 {
+    fn from(old: OrderHeaderV5) -> OrderHeaderV6 {
+        OrderHeaderV6 {
+            id: old.id,
+            business_id: old.business_id,
+            line_count: old.line_count,
+            customer: None,
+            discount: None,
+            subtotal: old.subtotal,
+            tax_line_count: old.tax_line_count,
+            total_price: old.total_price,
+            exchange_rate: old.exchange_rate,
+            status: old.status,
+            status_history: old.status_history,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+// layout version 4, from before orders were taxed
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct OrderHeaderV4 {
+    id: OrderId,
+    business_id: BusinessId,
+    line_count: u32,
+    total_price: Money,
+    exchange_rate: Option<ExchangeRate>,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl From<OrderHeaderV4> for OrderHeaderV5 {
+    fn from(old: OrderHeaderV4) -> OrderHeaderV5 {
+        OrderHeaderV5 {
+            id: old.id,
+            business_id: old.business_id,
+            line_count: old.line_count,
+            subtotal: old.total_price.clone(),
+            tax_line_count: 0,
+            total_price: old.total_price,
+            exchange_rate: old.exchange_rate,
+            status: old.status,
+            status_history: old.status_history,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct OrderHeaderV3 {
+    id: OrderId,
+    business_id: BusinessId,
+    line_count: u32,
+    total_price: Money,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl From<OrderHeaderV3> for OrderHeaderV4 {
+    fn from(old: OrderHeaderV3) -> OrderHeaderV4 {
+        OrderHeaderV4 {
+            id: old.id,
+            business_id: old.business_id,
+            line_count: old.line_count,
+            total_price: old.total_price,
+            exchange_rate: None,
+            status: old.status,
+            status_history: old.status_history,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct OrderHeaderV1 {
+    id: u64,
+    business_id: u64,
+    line_count: u32,
+    total_price: u64,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct OrderHeaderV2 {
+    id: OrderId,
+    business_id: BusinessId,
+    line_count: u32,
+    total_price: u64,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl From<OrderHeaderV2> for OrderHeaderV3 {
+    fn from(old: OrderHeaderV2) -> OrderHeaderV3 {
+        OrderHeaderV3 {
+            id: old.id,
+            business_id: old.business_id,
+            line_count: old.line_count,
+            total_price: Money::legacy(old.total_price),
+            status: old.status,
+            status_history: old.status_history,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+impl From<OrderHeaderV1> for OrderHeaderV2 {
+    fn from(old: OrderHeaderV1) -> OrderHeaderV2 {
+        OrderHeaderV2 {
+            id: OrderId::new(old.id),
+            business_id: BusinessId::new(old.business_id),
+            line_count: old.line_count,
+            total_price: old.total_price,
+            status: old.status,
+            status_history: old.status_history,
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+impl Storable for OrderHeader {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for OrderHeader {
+    const MAX_SIZE: u32 = 1024; // Adjust the maximum size as needed
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// orders as they were stored before schema version 2, with every line inline. They are
+// still read from their old map until `migrate_records` has moved them.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct LegacyOrder {
+    id: u64,
+    business_id: u64,
+    lines: Vec<OrderLineV1>,
+    total_price: u64,
+    status: OrderStatus,
+    status_history: Vec<StatusChange>,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl From<LegacyOrder> for Order {
+    fn from(legacy: LegacyOrder) -> Order {
+        Order {
+            id: OrderId::new(legacy.id),
+            business_id: BusinessId::new(legacy.business_id),
+            lines: legacy
+                .lines
+                .into_iter()
+                .map(|line| OrderLine::from(OrderLineV2::from(line)))
+                .collect(),
+            customer_id: None,
+            discount: None,
+            subtotal: Money::legacy(legacy.total_price),
+            tax_lines: Vec::new(),
+            total_price: Money::legacy(legacy.total_price),
+            exchange_rate: None,
+            payment: None,
+            status: legacy.status,
+            status_history: legacy.status_history,
+            created_at: legacy.created_at,
+            updated_at: legacy.updated_at,
+        }
+    }
+}
+
+impl Versioned for LegacyOrder {
+    const KIND: &'static str = "legacy order";
+    const VERSION: u8 = 1;
+
+    fn decode_legacy(version: u8, bytes: &[u8]) -> Result<Self, String> {
+        match version {
+            0 => Decode!(bytes, LegacyOrderV0)
+                .map(LegacyOrder::from)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!("unknown layout version {}", version)),
+        }
+    }
+}
+
+// layout version 0, as the first release stored orders: a copy of every product ordered, once
+// per unit, and no business or status
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct LegacyOrderV0 {
+    id: u64,
+    products: Vec<ProductV0>,
+    total_price: u64,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+// copies of the same product at the same price become one line. Like their products, these
+// orders don't belong to any business.
+impl From<LegacyOrderV0> for LegacyOrder {
+    fn from(old: LegacyOrderV0) -> LegacyOrder {
+        let mut lines: Vec<OrderLineV1> = Vec::new();
+        for product in old.products {
+            match lines
+                .iter_mut()
+                .find(|line| line.product_id == product.id && line.unit_price == product.price)
+            {
+                Some(line) => {
+                    line.quantity = line.quantity.saturating_add(1);
+                    line.line_total = line.line_total.saturating_add(product.price);
+                }
+                None => lines.push(OrderLineV1 {
+                    product_id: product.id,
+                    quantity: 1,
+                    unit_price: product.price,
+                    line_total: product.price,
+                }),
+            }
+        }
+        LegacyOrder {
+            id: old.id,
+            business_id: 0,
+            lines,
+            total_price: old.total_price,
+            status: OrderStatus::Pending,
+            status_history: Vec::new(),
+            created_at: old.created_at,
+            updated_at: old.updated_at,
+        }
+    }
+}
+
+impl Storable for LegacyOrder {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for LegacyOrder {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(candid::CandidType, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Default)]
+enum OrderStatus {
+    #[default]
+    Pending,
+    Confirmed,
+    Paid,
+    Shipped,
+    Delivered,
+    Cancelled,
+    PartiallyRefunded,
+    Refunded,
+}
+
+impl OrderStatus {
+    // money has moved once an order is paid, so from then on it can only be refunded, never cancelled
+    // (in part, once per approved return, or in full)
+    fn can_transition_to(self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        matches!(
+            (self, next),
+            (Pending, Confirmed)
+                | (Pending, Cancelled)
+                | (Confirmed, Paid)
+                | (Confirmed, Cancelled)
+                | (Paid, Shipped)
+                | (Paid, Refunded)
+                | (Shipped, Delivered)
+                | (Shipped, Refunded)
+                | (Delivered, Refunded)
+                | (Paid, PartiallyRefunded)
+                | (Shipped, PartiallyRefunded)
+                | (Delivered, PartiallyRefunded)
+                | (PartiallyRefunded, PartiallyRefunded)
+                | (PartiallyRefunded, Refunded)
+        )
+    }
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct StatusChange {
+    status: OrderStatus,
+    changed_by: Principal,
+    changed_at: u64,
+}
+
+// the unit price is copied from the product when the line is priced, so later
+// catalog changes don't alter orders that were already placed
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct OrderLine {
+    product_id: ProductId,
+    quantity: u32,
+    unit_price: Money,
+    line_total: Money,
+}
+
+impl Versioned for OrderLine {
+    const KIND: &'static str = "order line";
+    const VERSION: u8 = 3;
+
+    fn decode_legacy(version: u8, bytes: &[u8]) -> Result<Self, String> {
+        match version {
+            1 => Decode!(bytes, OrderLineV1)
+                .map(|old| OrderLine::from(OrderLineV2::from(old)))
+                .map_err(|e| e.to_string()),
+            2 => Decode!(bytes, OrderLineV2)
+                .map(OrderLine::from)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!("unknown layout version {}", version)),
+        }
+    }
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct OrderLineV1 {
+    product_id: u64,
+    quantity: u32,
+    unit_price: u64,
+    line_total: u64,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct OrderLineV2 {
+    product_id: ProductId,
+    quantity: u32,
+    unit_price: u64,
+    line_total: u64,
+}
+
+impl From<OrderLineV2> for OrderLine {
+    fn from(old: OrderLineV2) -> OrderLine {
+        OrderLine {
+            product_id: old.product_id,
+            quantity: old.quantity,
+            unit_price: Money::legacy(old.unit_price),
+            line_total: Money::legacy(old.line_total),
+        }
+    }
+}
+
+impl From<OrderLineV1> for OrderLineV2 {
+    fn from(old: OrderLineV1) -> OrderLineV2 {
+        OrderLineV2 {
+            product_id: ProductId::new(old.product_id),
+            quantity: old.quantity,
+            unit_price: old.unit_price,
+            line_total: old.line_total,
+        }
+    }
+}
+
+impl Storable for OrderLine {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for OrderLine {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// roles are ordered by privilege: each role can do everything the roles below it can
+#[derive(
+    candid::CandidType, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+enum Role {
+    Viewer,
+    Cashier,
+    Manager,
+    Owner,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Member {
+    business_id: BusinessId,
+    principal: Principal,
+    role: Role,
+    granted_at: u64,
+}
+
+impl Versioned for Member {
+    const KIND: &'static str = "member";
+    const VERSION: u8 = 2;
+
+    fn decode_legacy(version: u8, bytes: &[u8]) -> Result<Self, String> {
+        match version {
+            1 => Decode!(bytes, MemberV1).map(Member::from).map_err(|e| e.to_string()),
+            _ => Err(format!("unknown layout version {}", version)),
+        }
+    }
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct MemberV1 {
+    business_id: u64,
+    principal: Principal,
+    role: Role,
+    granted_at: u64,
+}
+
+impl From<MemberV1> for Member {
+    fn from(old: MemberV1) -> Member {
+        Member {
+            business_id: BusinessId::new(old.business_id),
+            principal: old.principal,
+            role: old.role,
+            granted_at: old.granted_at,
+        }
+    }
+}
+
+impl Storable for Member {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for Member {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// principals are at most 29 bytes long, which lets them be used inside map keys
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct StorablePrincipal(Principal);
+
+impl Storable for StorablePrincipal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_slice())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        StorablePrincipal(Principal::from_slice(bytes.as_ref()))
+    }
+}
+
+// the empty principal sorts before every other one, which makes it a handy range start
+impl Default for StorablePrincipal {
+    fn default() -> Self {
+        StorablePrincipal(Principal::management_canister())
+    }
+}
+
+impl BoundedStorable for StorablePrincipal {
+    const MAX_SIZE: u32 = 29;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// collects every problem with a payload so the caller can fix them all in one go
+#[derive(Default)]
+struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    fn error(&mut self, field: &str, msg: String) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            msg,
+        });
+    }
+
+    fn text(&mut self, field: &str, value: &str, required: bool, max_len: usize) {
+        if required && value.trim().is_empty() {
+            self.error(field, "must not be empty".to_string());
+        }
+        let len = value.chars().count();
+        if len > max_len {
+            self.error(
+                field,
+                format!("must be at most {} characters long, got {}", max_len, len),
+            );
+        }
+    }
+
+    fn range<T: PartialOrd + std::fmt::Display>(&mut self, field: &str, value: T, min: T, max: T) {
+        if value < min || value > max {
+            self.error(
+                field,
+                format!("must be between {} and {}, got {}", min, max, value),
+            );
+        }
+    }
+
+    fn money(&mut self, field: &str, value: &Money) {
+        self.range(&format!("{}.amount", field), value.amount, 1, u64::MAX);
+        self.currency_code(&format!("{}.currency", field), &value.currency);
+        self.range(&format!("{}.decimals", field), value.decimals, 0, MAX_DECIMALS);
+    }
+
+    fn currency(&mut self, field: &str, value: &Currency) {
+        self.currency_code(&format!("{}.code", field), &value.code);
+        self.range(&format!("{}.decimals", field), value.decimals, 0, MAX_DECIMALS);
+    }
+
+    fn coupon_code(&mut self, field: &str, code: &str) {
+        self.text(field, code, true, MAX_COUPON_CODE_LEN);
+        if !code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            self.error(field, "must be letters, digits, '-' and '_'".to_string());
+        }
+    }
+
+    fn account_code(&mut self, field: &str, code: &str) {
+        self.text(field, code, true, MAX_ACCOUNT_CODE_LEN);
+        if !code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            self.error(field, "must be letters, digits, '-' and '_'".to_string());
+        }
+    }
+
+    fn currency_code(&mut self, field: &str, code: &str) {
+        self.text(field, code, true, MAX_CURRENCY_LEN);
+        if !code.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            self.error(field, "must be upper case letters and digits, e.g. USD".to_string());
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+        Err(Error::InvalidInput {
+            msg: format!("the payload has {} invalid field(s)", self.errors.len()),
+            fields: self.errors,
+        })
+    }
+}
+
+trait Validate {
+    fn validate(&self) -> Result<(), Error>;
+}
+
+impl Validate for BusinessPayload {
+    fn validate(&self) -> Result<(), Error> {
+        let mut v = Validator::default();
+        v.text("name", &self.name, true, MAX_NAME_LEN);
+        v.text("description", &self.description, false, MAX_DESCRIPTION_LEN);
+        v.text("address", &self.address, true, MAX_ADDRESS_LEN);
+        v.currency("base_currency", &self.base_currency);
+        v.finish()
+    }
+}
+
+impl Validate for ProductPayload {
+    fn validate(&self) -> Result<(), Error> {
+        let mut v = Validator::default();
+        v.text("name", &self.name, true, MAX_NAME_LEN);
+        v.text("description", &self.description, false, MAX_DESCRIPTION_LEN);
+        v.money("price", &self.price);
+        v.range("prices", self.prices.len(), 0, MAX_EXTRA_PRICES);
+        if let Some(category) = &self.tax_category {
+            v.text("tax_category", category, true, MAX_TAX_LABEL_LEN);
+        }
+        for (i, price) in self.prices.iter().enumerate() {
+            v.money(&format!("prices[{}]", i), price);
+        }
+        v.finish()
+    }
+}
+
+impl ProductPayload {
+    // the main price has to be in the business's base currency, the others one per currency
+    fn validate_currencies(&self, base: &Currency) -> Result<(), Error> {
+        let mut v = Validator::default();
+        if self.price.currency() != *base {
+            v.error(
+                "price",
+                format!(
+                    "must be in the base currency of the business, {} with {} decimals",
+                    base.code, base.decimals
+                ),
+            );
+        }
+        let mut seen = std::collections::BTreeSet::new();
+        seen.insert(base.code.clone());
+        for (i, price) in self.prices.iter().enumerate() {
+            if !seen.insert(price.currency.clone()) {
+                v.error(
+                    &format!("prices[{}].currency", i),
+                    format!("{} already has a price", price.currency),
+                );
+            }
+        }
+        v.finish()
+    }
+}
+
+impl Validate for OrderPayload {
+    fn validate(&self) -> Result<(), Error> {
+        let mut v = Validator::default();
+        v.range("lines", self.lines.len(), 1, MAX_ORDER_LINES);
+        if let Some(currency) = &self.currency {
+            v.currency_code("currency", currency);
+        }
+        if let Some(code) = &self.coupon_code {
+            v.coupon_code("coupon_code", code);
+        }
+        let mut seen = std::collections::BTreeSet::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            let field = format!("lines[{}].quantity", i);
+            v.range(&field, line.quantity, 1, MAX_LINE_QUANTITY);
+            if !seen.insert(line.product_id) {
+                v.error(
+                    &format!("lines[{}].product_id", i),
+                    format!("product with id={} is listed more than once", line.product_id),
+                );
+            }
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CustomerPayload {
+    fn validate(&self) -> Result<(), Error> {
+        let mut v = Validator::default();
+        v.text("name", &self.name, true, MAX_NAME_LEN);
+        v.text("email", &self.email, false, MAX_CONTACT_LEN);
+        if !self.email.is_empty() && !self.email.contains('@') {
+            v.error("email", "must be an email address".to_string());
+        }
+        v.text("phone", &self.phone, false, MAX_CONTACT_LEN);
+        v.text("address", &self.address, false, MAX_ADDRESS_LEN);
+        v.finish()
+    }
+}
+
+impl Validate for AccountPayload {
+    fn validate(&self) -> Result<(), Error> {
+        let mut v = Validator::default();
+        v.account_code("code", &self.code);
+        v.text("name", &self.name, true, MAX_NAME_LEN);
+        v.finish()
+    }
+}
+
+// the accounts are checked against the business's chart when the entry is posted
+impl Validate for JournalEntryPayload {
+    fn validate(&self) -> Result<(), Error> {
+        let mut v = Validator::default();
+        v.currency("currency", &self.currency);
+        v.text("memo", &self.memo, true, MAX_DESCRIPTION_LEN);
+        if let Some(date) = self.date {
+            v.range("date", date, 0, time());
+        }
+        v.range("lines", self.lines.len(), 2, MAX_JOURNAL_LINES);
+        let mut debits = 0u128;
+        let mut credits = 0u128;
+        for (i, line) in self.lines.iter().enumerate() {
+            v.account_code(&format!("lines[{}].account", i), &line.account);
+            if (line.debit == 0) == (line.credit == 0) {
+                v.error(
+                    &format!("lines[{}]", i),
+                    "must have either a debit or a credit".to_string(),
+                );
+            }
+            debits += line.debit as u128;
+            credits += line.credit as u128;
+        }
+        if debits != credits {
+            v.error(
+                "lines",
+                format!("the debits of {} don't match the credits of {}", debits, credits),
+            );
+        }
+        v.finish()
+    }
+}
+
+impl Validate for ReturnPayload {
+    fn validate(&self) -> Result<(), Error> {
+        let mut v = Validator::default();
+        v.range("lines", self.lines.len(), 1, MAX_RETURN_LINES);
+        for (i, line) in self.lines.iter().enumerate() {
+            v.range(&format!("lines[{}].quantity", i), line.quantity, 1, MAX_LINE_QUANTITY);
+            v.text(&format!("lines[{}].reason", i), &line.reason, true, MAX_REASON_LEN);
+        }
+        if let Some(amount) = &self.refund_amount {
+            v.money("refund_amount", amount);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for CouponPayload {
+    fn validate(&self) -> Result<(), Error> {
+        let mut v = Validator::default();
+        v.coupon_code("code", &self.code);
+        match &self.discount {
+            Discount::Percentage(rate) => v.range("discount", *rate as u64, 1, RATE_SCALE),
+            Discount::Fixed(amount) => v.money("discount", amount),
+        }
+        if let Some(valid_until) = self.valid_until {
+            if valid_until < self.valid_from {
+                v.error("valid_until", "must not be before valid_from".to_string());
+            }
+        }
+        if let Some(max) = self.max_redemptions {
+            v.range("max_redemptions", max, 1, u32::MAX);
+        }
+        if let Some(max) = self.max_redemptions_per_customer {
+            v.range("max_redemptions_per_customer", max, 1, u32::MAX);
+        }
+        if let Some(min) = &self.min_order_value {
+            v.money("min_order_value", min);
+        }
+        v.finish()
+    }
+}
+
+impl Validate for Vec<TaxRate> {
+    fn validate(&self) -> Result<(), Error> {
+        let mut v = Validator::default();
+        v.range("rates", self.len(), 0, MAX_TAX_RATES);
+        for (i, rate) in self.iter().enumerate() {
+            v.text(&format!("rates[{}].name", i), &rate.name, true, MAX_TAX_LABEL_LEN);
+            v.text(
+                &format!("rates[{}].jurisdiction", i),
+                &rate.jurisdiction,
+                true,
+                MAX_TAX_LABEL_LEN,
+            );
+            v.text(&format!("rates[{}].category", i), &rate.category, true, MAX_TAX_LABEL_LEN);
+            v.range(&format!("rates[{}].rate", i), rate.rate as u64, 0, RATE_SCALE);
+        }
+        v.finish()
+    }
+}
+
+// records are bounded in stable memory, so anything that would not fit is rejected before
+// it can make the insert trap
+fn ensure_fits<T: Versioned + BoundedStorable>(record: &T) -> Result<(), Error> {
+    let size = record.to_bytes().len();
+    if size > T::MAX_SIZE as usize {
+        return Err(Error::invalid_field(
+            T::KIND,
+            &format!(
+                "takes {} bytes when stored, above the limit of {}",
+                size,
+                T::MAX_SIZE
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// `next_cursor` is the id to pass as `cursor` to fetch the following page, or none on the last page
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct Page<T, K> {
+    items: Vec<T>,
+    next_cursor: Option<K>,
+}
+
+#[derive(CandidType, Clone, Serialize, Deserialize, Default)]
+struct MigrationState {
+    schema_version: u32,
+    // set while stored records are being rewritten in their current layout
+    rewrite: Option<RewriteCursor>,
+}
+
+impl Versioned for MigrationState {
+    const KIND: &'static str = "migration state";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for MigrationState {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+#[derive(CandidType, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+enum RecordKind {
+    Business,
+    Product,
+    Order,
+    OrderLine,
+    Member,
+    LegacyOrder,
+    // not a rewrite: counts orders into the sales totals
+    OrderSales,
+    // not a rewrite: links the buyers orders named by principal to customers
+    OrderBuyer,
+    // not a rewrite: moves the redemptions counted per principal to customers
+    PrincipalRedemption,
+}
+
+impl RecordKind {
+    fn next(self) -> Option<RecordKind> {
+        match self {
+            RecordKind::Business => Some(RecordKind::Product),
+            RecordKind::Product => Some(RecordKind::Order),
+            RecordKind::Order => Some(RecordKind::OrderLine),
+            RecordKind::OrderLine => Some(RecordKind::Member),
+            RecordKind::Member => Some(RecordKind::LegacyOrder),
+            RecordKind::LegacyOrder => Some(RecordKind::OrderSales),
+            RecordKind::OrderSales => Some(RecordKind::OrderBuyer),
+            RecordKind::OrderBuyer => Some(RecordKind::PrincipalRedemption),
+            RecordKind::PrincipalRedemption => None,
+        }
+    }
+}
+
+// the rewrite resumes at `next_key` of `kind`; order lines are keyed by their order id and
+// members by their business id
+#[derive(CandidType, Clone, Serialize, Deserialize)]
+struct RewriteCursor {
+    kind: RecordKind,
+    next_key: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Customer {
+    id: CustomerId,
+    business_id: BusinessId,
+    name: String,
+    email: String,
+    phone: String,
+    address: String,
+    // lets the customer read their own record, orders and summary
+    principal: Option<Principal>,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl Versioned for Customer {
+    const KIND: &'static str = "customer";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for Customer {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for Customer {
+    const MAX_SIZE: u32 = 1024;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// lifetime spend is kept per currency, since a customer may have paid in several
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct CustomerSummary {
+    customer_id: CustomerId,
+    order_count: u64,
+    lifetime_spend: Vec<Money>,
+    last_order_at: Option<u64>,
+}
+
+#[derive(candid::CandidType, Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum Discount {
+    // parts per million of the line totals
+    Percentage(u32),
+    // taken off the line totals; it has to be in the order's currency
+    Fixed(Money),
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Coupon {
+    id: CouponId,
+    business_id: BusinessId,
+    // unique within the business and matched case-insensitively; stored in upper case
+    code: String,
+    discount: Discount,
+    valid_from: u64,
+    valid_until: Option<u64>,
+    max_redemptions: Option<u32>,
+    max_redemptions_per_customer: Option<u32>,
+    // compared with the line totals before the discount, in the order's currency
+    min_order_value: Option<Money>,
+    redemption_count: u32,
+    created_at: u64,
+    updated_at: Option<u64>,
+}
+
+impl Versioned for Coupon {
+    const KIND: &'static str = "coupon";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for Coupon {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for Coupon {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// a coupon as it was applied to an order
+#[derive(candid::CandidType, Clone, Debug, Serialize, Deserialize)]
+struct DiscountLine {
+    coupon_id: CouponId,
+    code: String,
+    discount: Discount,
+    amount: Money,
+}
+
+// coupon, account and currency codes are at most `MAX_COUPON_CODE_LEN` bytes long, which lets
+// them be used inside map keys
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+struct StorableCode(String);
+
+impl Storable for StorableCode {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_bytes())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        StorableCode(String::from_utf8_lossy(bytes.as_ref()).into_owned())
+    }
+}
+
+impl BoundedStorable for StorableCode {
+    const MAX_SIZE: u32 = MAX_COUPON_CODE_LEN as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// a tax a business charges on products of one tax category, in parts per million of the net
+// price. Inclusive taxes are already part of the product prices, exclusive ones come on top.
+#[derive(candid::CandidType, Clone, Debug, Serialize, Deserialize, Default)]
+struct TaxRate {
+    // as printed on receipts, e.g. "VAT"
+    name: String,
+    // where the tax is levied, e.g. "DE" or "US-NY"
+    jurisdiction: String,
+    category: String,
+    rate: u32,
+    inclusive: bool,
+}
+
+impl Versioned for TaxRate {
+    const KIND: &'static str = "tax rate";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for TaxRate {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for TaxRate {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// ICRC-1 and ICRC-2 ledger types, as they appear in the ledgers' Candid interface
+#[derive(candid::CandidType, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Account {
+    owner: Principal,
+    subaccount: Option<Vec<u8>>,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct TransferFromArgs {
+    spender_subaccount: Option<Vec<u8>>,
+    from: Account,
+    to: Account,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct TransferArg {
+    from_subaccount: Option<Vec<u8>>,
+    to: Account,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(candid::CandidType, Debug, Deserialize)]
+enum TransferError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(candid::CandidType, Debug, Deserialize)]
+enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+// the ICRC ledger that holds the token of one currency; orders in that currency are paid on it
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct LedgerConfig {
+    currency: Currency,
+    ledger: Principal,
+    updated_by: Principal,
+    updated_at: u64,
+}
+
+impl Versioned for LedgerConfig {
+    const KIND: &'static str = "ledger";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for LedgerConfig {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for LedgerConfig {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(candid::CandidType, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum PaymentMethod {
+    // pulled from the buyer's account with an ICRC-2 approval
+    Approval,
+    // sent by the buyer to the order's deposit account and swept from there
+    Deposit,
+}
+
+// a payment collected on a ledger for an order
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Payment {
+    method: PaymentMethod,
+    ledger: Principal,
+    from: Account,
+    to: Account,
+    amount: Money,
+    // received on top of `amount` and owed back to the buyer
+    overpaid: Money,
+    block_index: Nat,
+    paid_at: u64,
+}
+
+impl Versioned for Payment {
+    const KIND: &'static str = "payment";
+    const VERSION: u8 = 2;
+
+    fn decode_legacy(version: u8, bytes: &[u8]) -> Result<Self, String> {
+        match version {
+            1 => Decode!(bytes, PaymentV1)
+                .map(Payment::from)
+                .map_err(|e| e.to_string()),
+            _ => Err(format!("unknown layout version {}", version)),
+        }
+    }
+}
+
+// layout version 1, from before orders could be paid by deposit
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct PaymentV1 {
+    ledger: Principal,
+    from: Account,
+    to: Account,
+    amount: Money,
+    block_index: Nat,
+    paid_at: u64,
+}
+
+impl From<PaymentV1> for Payment {
+    fn from(old: PaymentV1) -> Payment {
+        Payment {
+            method: PaymentMethod::Approval,
+            ledger: old.ledger,
+            from: old.from,
+            to: old.to,
+            overpaid: Money {
+                amount: 0,
+                ..old.amount.clone()
+            },
+            amount: old.amount,
+            block_index: old.block_index,
+            paid_at: old.paid_at,
+        }
+    }
+}
+
+impl Storable for Payment {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for Payment {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(candid::CandidType, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum ReturnStatus {
+    Requested,
+    Approved,
+    Rejected,
+}
+
+// units of one order line coming back
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct ReturnLine {
+    line_no: u32,
+    product_id: ProductId,
+    quantity: u32,
+    reason: String,
+}
+
+// a refund sent back on the ledger the order was paid on
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Refund {
+    ledger: Principal,
+    to: Account,
+    amount: Money,
+    block_index: Nat,
+    refunded_at: u64,
+}
+
+// a return merchandise authorization: units of an order coming back and what is refunded for them
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct ReturnAuthorization {
+    id: ReturnId,
+    order_id: OrderId,
+    business_id: BusinessId,
+    lines: Vec<ReturnLine>,
+    refund_amount: Money,
+    // where a ledger refund goes; the account the order was paid from when none
+    refund_to: Option<Account>,
+    restocked: bool,
+    status: ReturnStatus,
+    refund: Option<Refund>,
+    requested_by: Principal,
+    requested_at: u64,
+    decided_by: Option<Principal>,
+    decided_at: Option<u64>,
+}
+
+impl Versioned for ReturnAuthorization {
+    const KIND: &'static str = "return";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for ReturnAuthorization {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for ReturnAuthorization {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(candid::CandidType, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum InvoiceStatus {
+    Draft,
+    Issued,
+    Paid,
+    Overdue,
+    Void,
+}
+
+// who an invoice is from or to, as they were when it was drafted
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct BillingDetails {
+    name: String,
+    address: String,
+    email: Option<String>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Invoice {
+    id: InvoiceId,
+    business_id: BusinessId,
+    order_id: OrderId,
+    // the business's next invoice number, given when the invoice is issued
+    number: Option<u64>,
+    seller: BillingDetails,
+    // none when the order isn't for a known customer
+    buyer: Option<BillingDetails>,
+    subtotal: Money,
+    discount: Option<Money>,
+    tax_total: Money,
+    total: Money,
+    payment_terms_days: u32,
+    status: InvoiceStatus,
+    created_at: u64,
+    issued_at: Option<u64>,
+    due_at: Option<u64>,
+    paid_at: Option<u64>,
+    voided_at: Option<u64>,
+}
+
+impl Versioned for Invoice {
+    const KIND: &'static str = "invoice";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for Invoice {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for Invoice {
+    const MAX_SIZE: u32 = 2048;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(
+    candid::CandidType, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+enum AccountKind {
+    Asset,
+    Liability,
+    Equity,
+    Revenue,
+    Expense,
+}
+
+impl AccountKind {
+    // assets and expenses grow with debits, everything else with credits
+    fn debit_normal(self) -> bool {
+        matches!(self, AccountKind::Asset | AccountKind::Expense)
+    }
+}
+
+// an account in a business's chart of accounts. The built-in ones exist for every business
+// without being stored, and are only stored once they are renamed.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct ChartAccount {
+    business_id: BusinessId,
+    code: String,
+    name: String,
+    kind: AccountKind,
+    // posted to by the canister itself, so it can't change its kind
+    built_in: bool,
+}
+
+impl Versioned for ChartAccount {
+    const KIND: &'static str = "account";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for ChartAccount {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for ChartAccount {
+    const MAX_SIZE: u32 = 512;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// what made a journal entry; everything but manual entries is posted by the canister
+#[derive(candid::CandidType, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum EntrySource {
+    Manual,
+    // a confirmed order, owed by the buyer until it is paid
+    Sale(OrderId),
+    Cancellation(OrderId),
+    Payment(OrderId),
+    // received on top of an order's total and owed back to the buyer
+    Overpayment(OrderId),
+    Refund {
+        order_id: OrderId,
+        return_id: Option<ReturnId>,
+    },
+}
+
+impl EntrySource {
+    fn order_id(&self) -> Option<OrderId> {
+        match self {
+            EntrySource::Manual => None,
+            EntrySource::Sale(id)
+            | EntrySource::Cancellation(id)
+            | EntrySource::Payment(id)
+            | EntrySource::Overpayment(id) => Some(*id),
+            EntrySource::Refund { order_id, .. } => Some(*order_id),
+        }
+    }
+}
+
+// exactly one of `debit` and `credit` is set
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct JournalLine {
+    account: String,
+    debit: u64,
+    credit: u64,
+}
+
+// a business's journal entries are keyed by the date they take effect
+type JournalKey = ((BusinessId, u64), EntryId);
+
+// a balanced journal entry: its debits and credits add up to the same amount of `currency`
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    id: EntryId,
+    business_id: BusinessId,
+    currency: Currency,
+    // when the entry takes effect, which is what the reports go by
+    date: u64,
+    memo: String,
+    source: EntrySource,
+    lines: Vec<JournalLine>,
+    posted_by: Principal,
+    posted_at: u64,
+}
+
+impl Versioned for JournalEntry {
+    const KIND: &'static str = "journal entry";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for JournalEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for JournalEntry {
+    const MAX_SIZE: u32 = 4096;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// an account's debits and credits over a period. `balance` is taken on the side the account
+// normally carries, so it is negative for e.g. an overdrawn asset or the sales returns account.
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct AccountBalance {
+    code: String,
+    name: String,
+    kind: AccountKind,
+    debit: u128,
+    credit: u128,
+    balance: i128,
+}
+
+// entries in different currencies don't add up, so every report comes once per currency
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct TrialBalance {
+    currency: Currency,
+    accounts: Vec<AccountBalance>,
+    total_debit: u128,
+    total_credit: u128,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct ProfitAndLoss {
+    currency: Currency,
+    revenue: Vec<AccountBalance>,
+    expenses: Vec<AccountBalance>,
+    total_revenue: i128,
+    total_expenses: i128,
+    net_income: i128,
+}
+
+// the period's net income isn't closed into an equity account, so it is given on its own;
+// assets equal liabilities plus equity plus net income
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct BalanceSheet {
+    currency: Currency,
+    assets: Vec<AccountBalance>,
+    liabilities: Vec<AccountBalance>,
+    equity: Vec<AccountBalance>,
+    total_assets: i128,
+    total_liabilities: i128,
+    total_equity: i128,
+    net_income: i128,
+}
+
+// what a business, or one of its products, sold in one currency on one UTC day
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct DailySales {
+    order_count: u64,
+    units: u64,
+    revenue: Money,
+}
+
+impl Versioned for DailySales {
+    const KIND: &'static str = "daily sales";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for DailySales {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for DailySales {
+    const MAX_SIZE: u32 = 128;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(candid::CandidType, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum Granularity {
+    Day,
+    // weeks start on Monday
+    Week,
+    Month,
+}
+
+// `start` is the first nanosecond of the period, in UTC
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct SalesPeriod {
+    start: u64,
+    order_count: u64,
+    units: u64,
+    revenue: Money,
+    average_order_value: Money,
+}
+
+// product revenue is taken from the line totals, before any discount on the order
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct ProductSales {
+    product_id: ProductId,
+    units: u64,
+    revenue: Money,
+}
+
+// periods and products come once per currency they were sold in
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct SalesReport {
+    periods: Vec<SalesPeriod>,
+    // the best sellers by units over the whole range
+    top_products: Vec<ProductSales>,
+}
+
+// sales totals are keyed by business and day, then by currency or by product and currency
+type SalesKey = ((BusinessId, u64), StorableCode);
+type ProductSalesKey = ((BusinessId, u64), (ProductId, StorableCode));
+
+// one change to a stored record, made by `caller` through the update method `method`. The
+// snapshots are the record as JSON; `before` is none for a record that was created and `after`
+// for one that was deleted.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct AuditEvent {
+    // the event's position in the audit log
+    id: u64,
+    caller: Principal,
+    method: String,
+    entity_kind: String,
+    entity_id: String,
+    // none for canister-wide settings such as ledgers and exchange rates
+    business_id: Option<BusinessId>,
+    timestamp: u64,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl Versioned for AuditEvent {
+    const KIND: &'static str = "audit event";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for AuditEvent {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+// a record whose changes go to the audit log
+trait Audited: serde::Serialize {
+    const ENTITY_KIND: &'static str;
+
+    fn entity_id(&self) -> String;
+    fn audit_business_id(&self) -> Option<BusinessId>;
+}
+
+macro_rules! audited {
+    ($name:ty, $kind:literal, |$record:ident| $id:expr, $business_id:expr) => {
+        impl Audited for $name {
+            const ENTITY_KIND: &'static str = $kind;
+
+            fn entity_id(&self) -> String {
+                let $record = self;
+                $id.to_string()
+            }
+
+            // canister-wide records don't look at themselves
+            #[allow(unused_variables)]
+            fn audit_business_id(&self) -> Option<BusinessId> {
+                let $record = self;
+                $business_id
+            }
+        }
+    };
+}
+
+// a business's tax rates are replaced as a whole, so they are audited as one record
+#[derive(Serialize)]
+struct TaxRateTable {
+    business_id: BusinessId,
+    rates: Vec<TaxRate>,
+}
+
+audited!(Business, "business", |business| business.id, Some(business.id));
+audited!(Member, "member", |member| member.principal, Some(member.business_id));
+audited!(Product, "product", |product| product.id, Some(product.business_id));
+audited!(Order, "order", |order| order.id, Some(order.business_id));
+audited!(Customer, "customer", |customer| customer.id, Some(customer.business_id));
+audited!(Coupon, "coupon", |coupon| coupon.id, Some(coupon.business_id));
+audited!(TaxRateTable, "tax rates", |table| table.business_id, Some(table.business_id));
+audited!(LedgerConfig, "ledger", |config| config.currency.code, None);
+audited!(
+    ExchangeRate,
+    "exchange rate",
+    |rate| format!("{}/{}", rate.base.code, rate.quote.code),
+    None
+);
+audited!(ReturnAuthorization, "return", |rma| rma.id, Some(rma.business_id));
+audited!(Invoice, "invoice", |invoice| invoice.id, Some(invoice.business_id));
+audited!(ChartAccount, "account", |account| account.code, Some(account.business_id));
+audited!(JournalEntry, "journal entry", |entry| entry.id, Some(entry.business_id));
+audited!(MigrationState, "migration state", |state| state.schema_version, None);
+
+// every field is optional and the ones given all have to match
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+struct AuditFilter {
+    business_id: Option<BusinessId>,
+    entity_kind: Option<String>,
+    entity_id: Option<String>,
+    caller: Option<Principal>,
+    from: Option<u64>,
+    to: Option<u64>,
+}
+
+impl AuditFilter {
+    fn matches(&self, event: &AuditEvent) -> bool {
+        self.business_id.is_none_or(|id| event.business_id == Some(id))
+            && self.entity_kind.as_ref().is_none_or(|kind| event.entity_kind == *kind)
+            && self.entity_id.as_ref().is_none_or(|id| event.entity_id == *id)
+            && self.caller.is_none_or(|caller| event.caller == caller)
+            && self.to.is_none_or(|to| event.timestamp <= to)
+    }
+}
+
+// marks an order as having a ledger call in flight until it is dropped
+struct PaymentGuard(OrderId);
+
+impl PaymentGuard {
+    fn acquire(id: OrderId) -> Result<PaymentGuard, Error> {
+        if !PAYMENTS_IN_FLIGHT.with(|orders| orders.borrow_mut().insert(id)) {
+            return Err(Error::Conflict {
+                msg: format!("a payment for the order with id={} is already in progress", id),
+            });
+        }
+        Ok(PaymentGuard(id))
+    }
+}
+
+impl Drop for PaymentGuard {
+    fn drop(&mut self) {
+        PAYMENTS_IN_FLIGHT.with(|orders| orders.borrow_mut().remove(&self.0));
+    }
+}
+
+// a tax rate as it was applied to an order, copied so later rate changes don't alter receipts
+#[derive(candid::CandidType, Clone, Debug, Serialize, Deserialize, Default)]
+struct TaxLine {
+    rate: TaxRate,
+    // the net amount of the order lines the rate applies to
+    taxable_amount: Money,
+    tax: Money,
+}
+
+impl Versioned for TaxLine {
+    const KIND: &'static str = "tax line";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for TaxLine {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+// a stored rate plus two amounts, each at most 10 bytes besides its currency code, and the
+// Candid types of the line and the amounts, which take under 64 bytes
+impl BoundedStorable for TaxLine {
+    const MAX_SIZE: u32 = TaxRate::MAX_SIZE + 2 * (10 + MAX_CURRENCY_LEN as u32) + 64;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+// one whole unit of `base` is worth `rate` / 10^RATE_DECIMALS whole units of `quote`
+#[derive(candid::CandidType, Clone, Debug, Serialize, Deserialize)]
+struct ExchangeRate {
+    base: Currency,
+    quote: Currency,
+    rate: u64,
+    updated_by: Principal,
+    updated_at: u64,
+}
+
+impl ExchangeRate {
+    // rounds half up to the smallest unit of the quote currency
+    fn convert(&self, price: &Money) -> Result<Money, Error> {
+        if price.currency() != self.base {
+            return Err(Error::CurrencyMismatch {
+                msg: format!(
+                    "a {}/{} rate can't convert {}",
+                    self.base.code, self.quote.code, price
+                ),
+            });
+        }
+        let overflow = || Error::LimitExceeded {
+            msg: format!("converting {} to {} overflows", price, self.quote.code),
+        };
+        let numerator = (price.amount as u128)
+            .checked_mul(self.rate as u128)
+            .and_then(|n| n.checked_mul(10u128.pow(self.quote.decimals as u32)))
+            .ok_or_else(overflow)?;
+        let denominator = 10u128.pow(RATE_DECIMALS + self.base.decimals as u32);
+        let amount = (numerator / denominator + u128::from(numerator % denominator * 2 >= denominator))
+            .try_into()
+            .map_err(|_| overflow())?;
+        Ok(Money {
+            amount,
+            currency: self.quote.code.clone(),
+            decimals: self.quote.decimals,
+        })
+    }
+}
+
+impl Versioned for ExchangeRate {
+    const KIND: &'static str = "exchange rate";
+    const VERSION: u8 = 1;
+}
+
+impl Storable for ExchangeRate {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        encode_versioned(self)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_versioned(bytes.as_ref())
+    }
+}
+
+impl BoundedStorable for ExchangeRate {
+    const MAX_SIZE: u32 = 256;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+struct RateKey {
+    base: String,
+    quote: String,
+}
+
+impl Storable for RateKey {
+    // laid out as the length of the base code (1 byte), the base code and the quote code
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(1 + self.base.len() + self.quote.len());
+        bytes.push(self.base.len() as u8);
+        bytes.extend_from_slice(self.base.as_bytes());
+        bytes.extend_from_slice(self.quote.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let split = 1 + bytes[0] as usize;
+        RateKey {
+            base: String::from_utf8_lossy(&bytes[1..split]).into_owned(),
+            quote: String::from_utf8_lossy(&bytes[split..]).into_owned(),
+        }
+    }
+}
+
+impl BoundedStorable for RateKey {
+    const MAX_SIZE: u32 = 1 + 2 * MAX_CURRENCY_LEN as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+enum SearchKind {
+    #[default]
+    Business = 0,
+    Product = 1,
+}
+
+// an entry of the inverted index: `entity_id` of `kind` contains `token`
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
+struct SearchKey {
+    kind: SearchKind,
+    token: String,
+    entity_id: u64,
+}
+
+impl Storable for SearchKey {
+    // laid out as kind (1 byte), entity id (8 bytes), token (the remaining bytes)
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(9 + self.token.len());
+        bytes.push(self.kind as u8);
+        bytes.extend_from_slice(&self.entity_id.to_be_bytes());
+        bytes.extend_from_slice(self.token.as_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let kind = match bytes[0] {
+            0 => SearchKind::Business,
+            _ => SearchKind::Product,
+        };
+        let mut entity_id = [0u8; 8];
+        entity_id.copy_from_slice(&bytes[1..9]);
+        SearchKey {
+            kind,
+            token: String::from_utf8_lossy(&bytes[9..]).into_owned(),
+            entity_id: u64::from_be_bytes(entity_id),
+        }
+    }
+}
+
+impl BoundedStorable for SearchKey {
+    const MAX_SIZE: u32 = 9 + MAX_TOKEN_LEN as u32;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+struct BusinessPayload {
+    name: String,
+    description: String,
+    address: String,
+    base_currency: Currency,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+struct ProductPayload {
+    business_id: BusinessId,
+    name: String,
+    description: String,
+    price: Money,
+    prices: Vec<Money>,
+    tax_category: Option<String>,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+struct OrderPayload {
+    business_id: BusinessId,
+    lines: Vec<OrderLinePayload>,
+    // code of the currency the buyer pays in; the business's base currency when none
+    currency: Option<String>,
+    customer_id: Option<CustomerId>,
+    coupon_code: Option<String>,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct CustomerPayload {
+    business_id: BusinessId,
+    name: String,
+    email: String,
+    phone: String,
+    address: String,
+    principal: Option<Principal>,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct AccountPayload {
+    code: String,
+    name: String,
+    kind: AccountKind,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct JournalEntryPayload {
+    business_id: BusinessId,
+    currency: Currency,
+    // now when none; entries can be backdated but not dated in the future
+    date: Option<u64>,
+    memo: String,
+    lines: Vec<JournalLine>,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct ReturnLinePayload {
+    line_no: u32,
+    quantity: u32,
+    reason: String,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct ReturnPayload {
+    order_id: OrderId,
+    lines: Vec<ReturnLinePayload>,
+    // the returned units' share of the order total when none
+    refund_amount: Option<Money>,
+    refund_to: Option<Account>,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize)]
+struct CouponPayload {
+    business_id: BusinessId,
+    code: String,
+    discount: Discount,
+    valid_from: u64,
+    valid_until: Option<u64>,
+    max_redemptions: Option<u32>,
+    max_redemptions_per_customer: Option<u32>,
+    min_order_value: Option<Money>,
+}
+
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+struct OrderLinePayload {
+    product_id: ProductId,
+    quantity: u32,
+}
+
+// every variant carries a human readable `msg`; clients should branch on the variant itself
+#[derive(candid::CandidType, Debug, Deserialize, Serialize)]
+enum Error {
+    NotFound { msg: String },
+    InvalidInput { msg: String, fields: Vec<FieldError> },
+    Unauthorized { msg: String },
+    Conflict { msg: String },
+    InvalidState { msg: String },
+    InsufficientStock { msg: String, items: Vec<StockShortage> },
+    CurrencyMismatch { msg: String },
+    PaymentFailed { msg: String },
+    LimitExceeded { msg: String },
+    Internal { msg: String },
+}
+
+#[derive(candid::CandidType, Debug, Deserialize, Serialize)]
+struct FieldError {
+    field: String,
+    msg: String,
+}
+
+impl Error {
+    fn invalid_field(field: &str, msg: &str) -> Error {
+        Error::InvalidInput {
+            msg: format!("invalid {}: {}", field, msg),
+            fields: vec![FieldError {
+                field: field.to_string(),
+                msg: msg.to_string(),
+            }],
+        }
+    }
+}
+
+#[derive(candid::CandidType, Debug, Deserialize, Serialize)]
+struct StockShortage {
+    product_id: ProductId,
+    requested: u64,
+    available: u64,
+}
+
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
+        MemoryManager::init(DefaultMemoryImpl::default())
+    );
+
+    // the counter all kinds of entity shared before schema version 3; now only read to seed
+    // the per-kind counters below
+    static ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static BUSINESS_STORAGE: RefCell<StableBTreeMap<BusinessId, Business, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(1)))
+    ));
+
+    static PRODUCT_STORAGE: RefCell<StableBTreeMap<ProductId, Product, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(2)))
+    ));
+
+    // orders written before schema version 2; only ever read and drained
+    static LEGACY_ORDER_STORAGE: RefCell<StableBTreeMap<u64, LegacyOrder, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(3)))
+    ));
+
+    static MEMBER_STORAGE: RefCell<StableBTreeMap<(BusinessId, StorablePrincipal), Member, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(4)))
+    ));
+
+    // (business_id, product_id) pairs, so a business's catalog can be read with a range scan
+    static PRODUCTS_BY_BUSINESS: RefCell<StableBTreeMap<(BusinessId, ProductId), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(5)))
+    ));
+
+    // (business_id, order_id) pairs
+    static ORDERS_BY_BUSINESS: RefCell<StableBTreeMap<(BusinessId, OrderId), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(6)))
+    ));
+
+    // inverted index over business and product text, valued by the token's weighted frequency
+    static SEARCH_INDEX: RefCell<StableBTreeMap<SearchKey, u32, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(7)))
+    ));
+
+    // a canister that predates this cell reads the default state, i.e. schema version 0
+    static MIGRATION_STATE: RefCell<Cell<MigrationState, Memory>> = RefCell::new(
+        Cell::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(8))),
+            MigrationState::default(),
+        )
+        .expect("Cannot create the migration state")
+    );
+
+    static ORDER_STORAGE: RefCell<StableBTreeMap<OrderId, OrderHeader, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(9)))
+    ));
+
+    // lines keyed by (order_id, line_no), line numbers running from 0 to the header's line_count
+    static ORDER_LINE_STORAGE: RefCell<StableBTreeMap<(OrderId, u32), OrderLine, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))
+    ));
+
+    // each counter holds the last id handed out for its kind of entity
+    static BUSINESS_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static PRODUCT_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static ORDER_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static EXCHANGE_RATES: RefCell<StableBTreeMap<RateKey, ExchangeRate, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14)))
+    ));
+
+    // a business's tax rates keyed by (business_id, position), in the order they were set
+    static TAX_RATES: RefCell<StableBTreeMap<(BusinessId, u32), TaxRate, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15)))
+    ));
+
+    static ORDER_TAX_LINE_STORAGE: RefCell<StableBTreeMap<(OrderId, u32), TaxLine, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16)))
+    ));
+
+    static COUPON_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static COUPON_STORAGE: RefCell<StableBTreeMap<CouponId, Coupon, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18)))
+    ));
+
+    // (business_id, code) pairs, so a coupon can be looked up by the code a buyer enters
+    static COUPONS_BY_CODE: RefCell<StableBTreeMap<(BusinessId, StorableCode), CouponId, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19)))
+    ));
+
+    // redemptions counted per principal before orders named a customer record; only ever read
+    // and drained
+    static PRINCIPAL_REDEMPTIONS: RefCell<StableBTreeMap<(CouponId, StorablePrincipal), u32, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20)))
+    ));
+
+    static CUSTOMER_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static CUSTOMER_STORAGE: RefCell<StableBTreeMap<CustomerId, Customer, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22)))
+    ));
+
+    static CUSTOMERS_BY_BUSINESS: RefCell<StableBTreeMap<(BusinessId, CustomerId), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23)))
+    ));
+
+    static ORDERS_BY_CUSTOMER: RefCell<StableBTreeMap<(CustomerId, OrderId), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24)))
+    ));
+
+    // how often each customer has redeemed a coupon
+    static COUPON_REDEMPTIONS: RefCell<StableBTreeMap<(CouponId, CustomerId), u32, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25)))
+    ));
+
+    // ledgers keyed by the code of the currency they hold
+    static LEDGERS: RefCell<StableBTreeMap<StorableCode, LedgerConfig, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26)))
+    ));
+
+    static ORDER_PAYMENTS: RefCell<StableBTreeMap<OrderId, Payment, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(27)))
+    ));
+
+    static RETURN_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(28))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static RETURN_STORAGE: RefCell<StableBTreeMap<ReturnId, ReturnAuthorization, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(29)))
+    ));
+
+    static RETURNS_BY_ORDER: RefCell<StableBTreeMap<(OrderId, ReturnId), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(30)))
+    ));
+
+    static INVOICE_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(31))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static INVOICE_STORAGE: RefCell<StableBTreeMap<InvoiceId, Invoice, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(32)))
+    ));
+
+    static INVOICES_BY_BUSINESS: RefCell<StableBTreeMap<(BusinessId, InvoiceId), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(33)))
+    ));
+
+    // the last invoice number each business has issued
+    static INVOICE_NUMBERS: RefCell<StableBTreeMap<BusinessId, u64, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(34)))
+    ));
+
+    // issued invoices keyed by their due date, so the overdue ones come first
+    static INVOICES_BY_DUE: RefCell<StableBTreeMap<(u64, InvoiceId), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(35)))
+    ));
+
+    static INVOICES_BY_ORDER: RefCell<StableBTreeMap<(OrderId, InvoiceId), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(36)))
+    ));
+
+    static JOURNAL_ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(37))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    // accounts businesses added or renamed; the built-in ones aren't stored until renamed
+    static ACCOUNT_STORAGE:
+        RefCell<StableBTreeMap<(BusinessId, StorableCode), ChartAccount, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(38)))
+    ));
+
+    static JOURNAL_STORAGE: RefCell<StableBTreeMap<EntryId, JournalEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(39)))
+    ));
+
+    static JOURNAL_BY_DATE: RefCell<StableBTreeMap<JournalKey, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(40)))
+    ));
+
+    static JOURNAL_BY_ORDER: RefCell<StableBTreeMap<(OrderId, EntryId), (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(41)))
+    ));
+
+    static SALES_BY_DAY: RefCell<StableBTreeMap<SalesKey, DailySales, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(42)))
+    ));
+
+    static PRODUCT_SALES_BY_DAY: RefCell<StableBTreeMap<ProductSalesKey, DailySales, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(43)))
+    ));
+
+    // orders that are in the sales totals
+    static SALES_COUNTED: RefCell<StableBTreeMap<OrderId, (), Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(44)))
+    ));
+
+    // every change made through an update method, oldest first
+    static AUDIT_LOG: RefCell<Log<AuditEvent, Memory, Memory>> = RefCell::new(
+        Log::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(45))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(46))),
+        )
+        .expect("Cannot create the audit log")
+    );
+
+    // orders with a ledger call in flight; nothing is in flight across an upgrade, so this
+    // lives on the heap
+    static PAYMENTS_IN_FLIGHT: RefCell<BTreeSet<OrderId>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+#[ic_cdk::init]
+fn init() {
+    // a fresh canister starts out with the current layout, so there is nothing to migrate
+    if let Err(e) = set_migration_state(MigrationState {
+        schema_version: SCHEMA_VERSION,
+        rewrite: None,
+    }) {
+        ic_cdk::trap(&format!("{:?}", e));
+    }
+    start_overdue_timer();
+}
+
+// every piece of state lives in stable structures already, so there is no `pre_upgrade` hook
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    let mut state = migration_state();
+    for (version, migrate) in MIGRATIONS {
+        if *version > state.schema_version {
+            migrate(&mut state);
+            state.schema_version = *version;
+        }
+    }
+    // failing the upgrade is better than running the new code against unmigrated data
+    if let Err(e) = set_migration_state(state) {
+        ic_cdk::trap(&format!("{:?}", e));
+    }
+    start_overdue_timer();
+}
+
+// timers don't survive an upgrade, so this runs after every install and upgrade
+fn start_overdue_timer() {
+    ic_cdk_timers::set_timer_interval(OVERDUE_CHECK_INTERVAL, || {
+        mark_overdue_invoices(time(), OVERDUE_BATCH_SIZE)
+    });
+}
+
+// Events matching the filter, newest first. Business owners can read the events of their
+// business; only controllers can read the canister-wide ones or leave out `business_id`.
+// A call reads at most `MAX_AUDIT_SCAN` events, so a page can come back short with a
+// `next_cursor` to carry on from.
+#[ic_cdk::query]
+fn get_audit_log(
+    filter: AuditFilter,
+    cursor: Option<u64>,
+    limit: u32,
+) -> Result<Page<AuditEvent, u64>, Error> {
+    if ensure_controller().is_err() {
+        match filter.business_id {
+            Some(business_id) => {
+                ensure_role(business_id, Role::Owner)?;
+            }
+            None => {
+                return Err(Error::Unauthorized {
+                    msg: "only controllers can read the audit log of every business".to_string(),
+                })
+            }
+        }
+    }
+    let page_size = page_size(limit)?;
+    AUDIT_LOG.with(|log| {
+        let log = log.borrow();
+        let mut next = match cursor {
+            Some(cursor) => cursor.saturating_add(1).min(log.len()),
+            None => log.len(),
+        };
+        let mut items = Vec::new();
+        let mut scanned = 0;
+        while next > 0 && items.len() < page_size && scanned < MAX_AUDIT_SCAN {
+            next -= 1;
+            scanned += 1;
+            let Some(event) = log.get(next) else {
+                continue;
+            };
+            // the log is in time order, so nothing older can match
+            if filter.from.is_some_and(|from| event.timestamp < from) {
+                next = 0;
+                break;
+            }
+            if filter.matches(&event) {
+                items.push(event);
+            }
+        }
+        Ok(Page {
+            items,
+            next_cursor: (next > 0).then(|| next - 1),
+        })
+    })
+}
+
+// Appends a change to the audit log; `before` and `after` are none for a record that was
+// created or deleted. An event that can't be written traps, which rolls the change back too.
+fn audit<T: Audited>(method: &str, before: Option<&T>, after: Option<&T>) {
+    let Some(record) = after.or(before) else {
+        return;
+    };
+    AUDIT_LOG.with(|log| {
+        let log = log.borrow();
+        let event = AuditEvent {
+            id: log.len(),
+            caller: caller(),
+            method: method.to_string(),
+            entity_kind: T::ENTITY_KIND.to_string(),
+            entity_id: record.entity_id(),
+            business_id: record.audit_business_id(),
+            timestamp: time(),
+            before: before.map(snapshot),
+            after: after.map(snapshot),
+        };
+        if let Err(e) = log.append(&event) {
+            ic_cdk::trap(&format!("couldn't write an audit event: {:?}", e));
+        }
+    });
+}
+
+fn snapshot<T: serde::Serialize>(record: &T) -> String {
+    serde_json::to_string(record)
+        .unwrap_or_else(|e| ic_cdk::trap(&format!("couldn't snapshot a record: {}", e)))
+}
+
+#[ic_cdk::query]
+fn get_migration_state() -> Result<MigrationState, Error> {
+    Ok(migration_state())
+}
+
+// rewrites up to `batch_size` records in their current layout. Records are readable in any
+// layout in the meantime, so controllers can call this until `rewrite` is cleared at their own pace.
+#[ic_cdk::update]
+fn migrate_records(batch_size: u32) -> Result<MigrationState, Error> {
+    ensure_controller()?;
+    let before = migration_state();
+    let mut state = before.clone();
+    let mut budget = batch_size.max(1) as usize;
+    while budget > 0 {
+        let Some(cursor) = state.rewrite.clone() else {
+            break;
+        };
+        let (rewritten, next_key) = match cursor.kind {
+            RecordKind::Business => BUSINESS_STORAGE
+                .with(|service| rewrite_records(&mut service.borrow_mut(), cursor.next_key, budget)),
+            RecordKind::Product => PRODUCT_STORAGE
+                .with(|service| rewrite_records(&mut service.borrow_mut(), cursor.next_key, budget)),
+            RecordKind::Order => ORDER_STORAGE
+                .with(|service| rewrite_records(&mut service.borrow_mut(), cursor.next_key, budget)),
+            RecordKind::OrderLine => rewrite_order_lines(cursor.next_key, budget),
+            RecordKind::Member => rewrite_members(cursor.next_key, budget),
+            RecordKind::LegacyOrder => move_legacy_orders(cursor.next_key, budget),
+            RecordKind::OrderSales => count_order_sales(cursor.next_key, budget),
+            RecordKind::OrderBuyer => link_order_buyers(cursor.next_key, budget),
+            RecordKind::PrincipalRedemption => move_principal_redemptions(budget),
+        };
+        budget = budget.saturating_sub(rewritten);
+        state.rewrite = match next_key {
+            Some(next_key) => Some(RewriteCursor {
+                kind: cursor.kind,
+                next_key,
+            }),
+            None => cursor.kind.next().map(|kind| RewriteCursor { kind, next_key: 0 }),
+        };
+    }
+    set_migration_state(state.clone())?;
+    audit("migrate_records", Some(&before), Some(&state));
+    Ok(state)
+}
+
+// schema version 1 wraps every record in a versioned envelope
+fn start_record_rewrite(state: &mut MigrationState) {
+    state.rewrite = Some(RewriteCursor {
+        kind: RecordKind::Business,
+        next_key: 0,
+    });
+}
+
+// schema version 2 moves order lines out of the order records into their own map
+fn start_order_split(state: &mut MigrationState) {
+    // a rewrite that is already underway ends with the legacy orders anyway
+    if state.rewrite.is_none() {
+        state.rewrite = Some(RewriteCursor {
+            kind: RecordKind::LegacyOrder,
+            next_key: 0,
+        });
+    }
+}
+
+// schema version 9 adds sales totals, which the orders placed before it have to be counted into
+fn start_sales_count(state: &mut MigrationState) {
+    // a rewrite that is already underway ends with the sales totals anyway
+    if state.rewrite.is_none() {
+        state.rewrite = Some(RewriteCursor {
+            kind: RecordKind::OrderSales,
+            next_key: 0,
+        });
+    }
+}
+
+// schema version 3 gives every kind of entity its own id counter and retypes the ids inside
+// stored records. Each counter starts from the old shared one, so existing ids are kept and
+// new ones can't collide with them.
+fn split_id_counters(state: &mut MigrationState) {
+    let last_id = ID_COUNTER.with(|counter| *counter.borrow().get());
+    for counter in [&BUSINESS_ID_COUNTER, &PRODUCT_ID_COUNTER, &ORDER_ID_COUNTER] {
+        counter.with(|counter| {
+            let current = *counter.borrow().get();
+            if let Err(e) = counter.borrow_mut().set(current.max(last_id)) {
+                ic_cdk::trap(&format!("cannot seed an id counter: {:?}", e));
+            }
+        });
+    }
+    if state.rewrite.is_none() {
+        start_record_rewrite(state);
+    }
+}
+
+// schema version 10 links the buyers that orders and coupon redemptions knew by principal,
+// before there were customer records, to the business's customer with that principal
+fn start_buyer_linking(state: &mut MigrationState) {
+    // a rewrite that is already underway ends with the linking anyway
+    if state.rewrite.is_none() {
+        state.rewrite = Some(RewriteCursor {
+            kind: RecordKind::OrderBuyer,
+            next_key: 0,
+        });
+    }
+}
+
+fn link_order_buyers(start: u64, limit: usize) -> (usize, Option<u64>) {
+    let mut headers: Vec<OrderHeader> = ORDER_STORAGE.with(|service| {
+        service
+            .borrow()
+            .range(OrderId::new(start)..)
+            .take(limit + 1)
+            .map(|(_, header)| header)
+            .collect()
+    });
+    let next_key = if headers.len() > limit {
+        headers.pop().map(|header| header.id.raw())
+    } else {
+        None
+    };
+    for header in headers.iter() {
+        if let Some(principal) = header.unlinked_buyer {
+            do_insert_order_header(&OrderHeader {
+                customer_id: Some(customer_for_principal(header.business_id, principal)),
+                unlinked_buyer: None,
+                ..header.clone()
+            });
+        }
+    }
+    (headers.len(), next_key)
+}
+
+// the counts are removed as they are moved, so every batch starts from the first one left
+fn move_principal_redemptions(limit: usize) -> (usize, Option<u64>) {
+    let batch: Vec<((CouponId, StorablePrincipal), u32)> = PRINCIPAL_REDEMPTIONS
+        .with(|service| service.borrow().iter().take(limit).collect());
+    for &((coupon_id, principal), count) in batch.iter() {
+        PRINCIPAL_REDEMPTIONS.with(|service| service.borrow_mut().remove(&(coupon_id, principal)));
+        // the counts of deleted coupons have nothing left to limit
+        let Some(coupon) = _get_coupon(&coupon_id) else {
+            continue;
+        };
+        let customer_id = customer_for_principal(coupon.business_id, principal.0);
+        let total = _get_redemption_count(coupon_id, customer_id).saturating_add(count);
+        COUPON_REDEMPTIONS
+            .with(|service| service.borrow_mut().insert((coupon_id, customer_id), total));
+    }
+    let done = PRINCIPAL_REDEMPTIONS.with(|service| service.borrow().is_empty());
+    (batch.len(), (!done).then_some(0))
+}
+
+// the business's customer linked to `principal`, added with the principal as their name if
+// there is none yet
+fn customer_for_principal(business_id: BusinessId, principal: Principal) -> CustomerId {
+    let existing = _list_customer_ids_by_business(business_id)
+        .iter()
+        .filter_map(_get_customer)
+        .find(|customer| customer.principal == Some(principal));
+    if let Some(customer) = existing {
+        return customer.id;
+    }
+    let id = next_id(&CUSTOMER_ID_COUNTER).unwrap_or_else(|e| ic_cdk::trap(&format!("{:?}", e)));
+    let customer = Customer {
+        id,
+        business_id,
+        name: principal.to_text(),
+        email: String::new(),
+        phone: String::new(),
+        address: String::new(),
+        principal: Some(principal),
+        created_at: time(),
+        updated_at: None,
+    };
+    do_insert_customer(&customer);
+    audit("migrate_records", None, Some(&customer));
+    id
+}
+
+// re-saves up to `limit` records from `start` on and returns how many were written along
+// with the key to resume from, or none once the map is done
+fn rewrite_records<K: EntityId, T: BoundedStorable>(
+    map: &mut StableBTreeMap<K, T, Memory>,
+    start: u64,
+    limit: usize,
+) -> (usize, Option<u64>) {
+    let mut batch: Vec<(K, T)> = map.range(K::new(start)..).take(limit + 1).collect();
+    let next_key = if batch.len() > limit {
+        batch.pop().map(|(key, _)| key.raw())
+    } else {
+        None
+    };
+    let rewritten = batch.len();
+    for (key, record) in batch {
+        map.insert(key, record);
+    }
+    (rewritten, next_key)
+}
+
+// lines are rewritten a whole order at a time, so a batch may slightly exceed `limit`
+fn rewrite_order_lines(start: u64, limit: usize) -> (usize, Option<u64>) {
+    let mut rewritten = 0;
+    let mut order_id = start;
+    loop {
+        if rewritten >= limit {
+            return (rewritten, Some(order_id));
+        }
+        let next = ORDER_LINE_STORAGE.with(|service| {
+            service
+                .borrow()
+                .range((OrderId::new(order_id), 0)..)
+                .next()
+                .map(|((id, _), _)| id)
+        });
+        let Some(id) = next else {
+            return (rewritten, None);
+        };
+        ORDER_LINE_STORAGE.with(|service| {
+            let mut service = service.borrow_mut();
+            let lines: Vec<((OrderId, u32), OrderLine)> = service
+                .range((id, 0)..=(id, u32::MAX))
+                .collect();
+            for (key, line) in lines {
+                service.insert(key, line);
+                rewritten += 1;
+            }
+        });
+        order_id = id.raw() + 1;
+    }
+}
+
+// members are rewritten a whole business at a time, so a batch may slightly exceed `limit`
+fn rewrite_members(start: u64, limit: usize) -> (usize, Option<u64>) {
+    let mut rewritten = 0;
+    let mut business_id = start;
+    loop {
+        if rewritten >= limit {
+            return (rewritten, Some(business_id));
+        }
+        let next = MEMBER_STORAGE.with(|service| {
+            service
+                .borrow()
+                .range((BusinessId::new(business_id), StorablePrincipal::default())..)
+                .next()
+                .map(|((id, _), _)| id)
+        });
+        let Some(id) = next else {
+            return (rewritten, None);
+        };
+        for member in _list_members(id) {
+            do_insert_member(&member);
+            rewritten += 1;
+        }
+        business_id = id.raw() + 1;
+    }
+}
+
+fn move_legacy_orders(start: u64, limit: usize) -> (usize, Option<u64>) {
+    let mut ids: Vec<u64> = LEGACY_ORDER_STORAGE.with(|service| {
+        service
+            .borrow()
+            .range(start..)
+            .take(limit + 1)
+            .map(|(id, _)| id)
+            .collect()
+    });
+    let next_key = if ids.len() > limit { ids.pop() } else { None };
+    for id in ids.iter() {
+        move_legacy_order(OrderId::new(*id));
+    }
+    (ids.len(), next_key)
+}
+
+fn move_legacy_order(id: OrderId) {
+    if let Some(legacy) =
+        LEGACY_ORDER_STORAGE.with(|service| service.borrow_mut().remove(&id.raw()))
+    {
+        do_insert_order(&Order::from(legacy));
+    }
+}
+
+fn migration_state() -> MigrationState {
+    MIGRATION_STATE.with(|cell| cell.borrow().get().clone())
+}
+
+fn set_migration_state(state: MigrationState) -> Result<(), Error> {
+    MIGRATION_STATE
+        .with(|cell| cell.borrow_mut().set(state))
+        .map(|_| ())
+        .map_err(|e| Error::Internal {
+            msg: format!("cannot save the migration state: {:?}", e),
+        })
+}
+
+#[ic_cdk::query]
+fn get_business(id: BusinessId) -> Result<Business, Error> {
+    match _get_business(&id) {
+        Some(business) => Ok(business),
+        None => Err(Error::NotFound {
+            msg: format!("a business with id={} not found", id),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn list_businesses(
+    cursor: Option<BusinessId>,
+    limit: u32,
+) -> Result<Page<Business, BusinessId>, Error> {
+    BUSINESS_STORAGE.with(|service| paginate(&service.borrow(), cursor, limit))
+}
+
+#[ic_cdk::update]
+fn add_business(business: BusinessPayload) -> Result<Business, Error> {
+    business.validate()?;
+    let mut business = Business {
+        id: BusinessId::default(),
+        owner: caller(),
+        name: business.name,
+        description: business.description,
+        address: business.address,
+        base_currency: business.base_currency,
+        created_at: time(),
+        updated_at: None,
+    };
+    ensure_fits(&business)?;
+    business.id = next_id(&BUSINESS_ID_COUNTER)?;
+    do_insert_business(&business);
+    index_business(&business);
+    let owner = Member {
+        business_id: business.id,
+        principal: business.owner,
+        role: Role::Owner,
+        granted_at: business.created_at,
+    };
+    do_insert_member(&owner);
+    audit("add_business", None, Some(&business));
+    audit("add_business", None, Some(&owner));
+    Ok(business)
+}
+
+#[ic_cdk::update]
+fn update_business(id: BusinessId, payload: BusinessPayload) -> Result<Business, Error> {
+    match BUSINESS_STORAGE.with(|service| service.borrow().get(&id)) {
+        Some(business) => {
+            ensure_role(business.id, Role::Manager)?;
+            payload.validate()?;
+            // existing prices are in the old currency, so it can only change on an empty catalog
+            if payload.base_currency != business.base_currency
+                && !_list_product_ids_by_business(id).is_empty()
+            {
+                return Err(Error::Conflict {
+                    msg: format!(
+                        "couldn't change the base currency of a business with id={}. \
+                         it still has products",
+                        id
+                    ),
+                });
+            }
+            let updated = Business {
+                name: payload.name,
+                description: payload.description,
+                address: payload.address,
+                base_currency: payload.base_currency,
+                updated_at: Some(time()),
+                ..business.clone()
+            };
+            ensure_fits(&updated)?;
+            unindex_business(&business);
+            do_insert_business(&updated);
+            index_business(&updated);
+            audit("update_business", Some(&business), Some(&updated));
+            Ok(updated)
+        }
+        None => Err(Error::NotFound {
+            msg: format!(
+                "couldn't update a business with id={}. business not found",
+                id
+            ),
+        }),
+    }
+}
+
+fn do_insert_business(business: &Business) {
+    BUSINESS_STORAGE
+        .with(|service| service.borrow_mut().insert(business.id, business.clone()));
+}
+
+// orders and journal entries are financial records, so a business that still has any can't be
+// deleted; its products, memberships, tax rates, coupons, customers and accounts are deleted
+// along with it
+#[ic_cdk::update]
+fn delete_business(id: BusinessId) -> Result<Business, Error> {
+    match _get_business(&id) {
+        Some(business) => {
+            ensure_role(business.id, Role::Owner)?;
+            let order_count = _list_order_ids_by_business(id).len();
+            if order_count > 0 {
+                return Err(Error::Conflict {
+                    msg: format!(
+                        "couldn't delete a business with id={}. it still has {} order(s)",
+                        id, order_count
+                    ),
+                });
+            }
+            if _has_journal_entries(id) {
+                return Err(Error::Conflict {
+                    msg: format!(
+                        "couldn't delete a business with id={}. its journal has entries",
+                        id
+                    ),
+                });
+            }
+            BUSINESS_STORAGE.with(|service| service.borrow_mut().remove(&id));
+            unindex_business(&business);
+            for product_id in _list_product_ids_by_business(id) {
+                if let Some(product) = do_remove_product(product_id) {
+                    audit("delete_business", Some(&product), None);
+                }
+            }
+            for member in _list_members(id) {
+                MEMBER_STORAGE.with(|service| {
+                    service
+                        .borrow_mut()
+                        .remove(&(id, StorablePrincipal(member.principal)))
+                });
+                audit("delete_business", Some(&member), None);
+            }
+            let rates = TaxRateTable {
+                business_id: id,
+                rates: _list_tax_rates(id),
+            };
+            do_set_tax_rates(id, &[]);
+            if !rates.rates.is_empty() {
+                audit("delete_business", Some(&rates), None);
+            }
+            for coupon in _list_coupon_ids_by_business(id).iter().filter_map(_get_coupon) {
+                do_remove_coupon(&coupon);
+                audit("delete_business", Some(&coupon), None);
+            }
+            for customer in _list_customer_ids_by_business(id)
+                .iter()
+                .filter_map(_get_customer)
+            {
+                do_remove_customer(&customer);
+                audit("delete_business", Some(&customer), None);
+            }
+            // built-in accounts are only stored once they have been renamed
+            for account in _list_accounts(id) {
+                let key = (id, StorableCode(account.code.clone()));
+                if ACCOUNT_STORAGE.with(|service| service.borrow_mut().remove(&key)).is_some() {
+                    audit("delete_business", Some(&account), None);
+                }
+            }
+            audit("delete_business", Some(&business), None);
+            Ok(business)
+        }
+        None => Err(Error::NotFound {
+            msg: format!(
+                "couldn't delete a business with id={}. business not found.",
+                id
+            ),
+        }),
+    }
+}
+
+#[ic_cdk::update]
+fn grant_role(business_id: BusinessId, principal: Principal, role: Role) -> Result<Member, Error> {
+    let business = match _get_business(&business_id) {
+        Some(business) => business,
+        None => {
+            return Err(Error::NotFound {
+                msg: format!(
+                    "couldn't grant a role on a business with id={}. business not found",
+                    business_id
+                ),
+            })
+        }
+    };
+    // businesses from the first release have no owner, so a controller has to hand them over
+    if business.owner != Principal::anonymous() || ensure_controller().is_err() {
+        ensure_role(business_id, Role::Owner)?;
+    }
+    if principal == business.owner && role != Role::Owner {
+        return Err(Error::Unauthorized {
+            msg: format!(
+                "the creator of the business with id={} must keep the Owner role",
+                business_id
+            ),
+        });
+    }
+    let member = Member {
+        business_id,
+        principal,
+        role,
+        granted_at: time(),
+    };
+    let before = MEMBER_STORAGE
+        .with(|service| service.borrow().get(&(business_id, StorablePrincipal(principal))));
+    do_insert_member(&member);
+    audit("grant_role", before.as_ref(), Some(&member));
+    Ok(member)
+}
+
+#[ic_cdk::update]
+fn revoke_role(business_id: BusinessId, principal: Principal) -> Result<Member, Error> {
+    let business = match _get_business(&business_id) {
+        Some(business) => business,
+        None => {
+            return Err(Error::NotFound {
+                msg: format!(
+                    "couldn't revoke a role on a business with id={}. business not found",
+                    business_id
+                ),
+            })
+        }
+    };
+    ensure_role(business_id, Role::Owner)?;
+    if principal == business.owner {
+        return Err(Error::Unauthorized {
+            msg: format!(
+                "the creator of the business with id={} must keep the Owner role",
+                business_id
+            ),
+        });
+    }
+    match MEMBER_STORAGE.with(|service| {
+        service
+            .borrow_mut()
+            .remove(&(business_id, StorablePrincipal(principal)))
+    }) {
+        Some(member) => {
+            audit("revoke_role", Some(&member), None);
+            Ok(member)
+        }
+        None => Err(Error::NotFound {
+            msg: format!(
+                "principal {} is not a member of the business with id={}",
+                principal, business_id
+            ),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn list_members(business_id: BusinessId) -> Result<Vec<Member>, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    Ok(_list_members(business_id))
+}
+
+fn do_insert_member(member: &Member) {
+    MEMBER_STORAGE.with(|service| {
+        service.borrow_mut().insert(
+            (member.business_id, StorablePrincipal(member.principal)),
+            member.clone(),
+        )
+    });
+}
+
+#[ic_cdk::query]
+fn get_customer(id: CustomerId) -> Result<Customer, Error> {
+    let customer = ensure_customer_exists(id)?;
+    ensure_customer_access(&customer)?;
+    Ok(customer)
+}
+
+#[ic_cdk::query]
+fn list_customers(
+    business_id: BusinessId,
+    cursor: Option<CustomerId>,
+    limit: u32,
+) -> Result<Page<Customer, CustomerId>, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    let page_size = page_size(limit)?;
+    let mut ids: Vec<CustomerId> = CUSTOMERS_BY_BUSINESS.with(|index| {
+        index
+            .borrow()
+            .range((business_id, cursor.unwrap_or_default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+            .take(page_size + 1)
+            .map(|((_, customer_id), _)| customer_id)
+            .collect()
+    });
+    let next_cursor = if ids.len() > page_size {
+        ids.pop()
+    } else {
+        None
+    };
+    Ok(Page {
+        items: ids.iter().filter_map(_get_customer).collect(),
+        next_cursor,
+    })
+}
+
+#[ic_cdk::update]
+fn add_customer(payload: CustomerPayload) -> Result<Customer, Error> {
+    ensure_business_exists(payload.business_id)?;
+    ensure_role(payload.business_id, Role::Cashier)?;
+    payload.validate()?;
+    let mut customer = Customer {
+        id: CustomerId::default(),
+        business_id: payload.business_id,
+        name: payload.name,
+        email: payload.email,
+        phone: payload.phone,
+        address: payload.address,
+        principal: payload.principal,
+        created_at: time(),
+        updated_at: None,
+    };
+    ensure_fits(&customer)?;
+    customer.id = next_id(&CUSTOMER_ID_COUNTER)?;
+    do_insert_customer(&customer);
+    audit("add_customer", None, Some(&customer));
+    Ok(customer)
+}
+
+// a customer can't move to another business, their orders stay where they are
+#[ic_cdk::update]
+fn update_customer(id: CustomerId, payload: CustomerPayload) -> Result<Customer, Error> {
+    match _get_customer(&id) {
+        Some(customer) => {
+            ensure_role(customer.business_id, Role::Cashier)?;
+            if payload.business_id != customer.business_id {
+                return Err(Error::invalid_field(
+                    "business_id",
+                    "a customer can't move to another business",
+                ));
+            }
+            payload.validate()?;
+            let updated = Customer {
+                name: payload.name,
+                email: payload.email,
+                phone: payload.phone,
+                address: payload.address,
+                principal: payload.principal,
+                updated_at: Some(time()),
+                ..customer.clone()
+            };
+            ensure_fits(&updated)?;
+            do_insert_customer(&updated);
+            audit("update_customer", Some(&customer), Some(&updated));
+            Ok(updated)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't update a customer with id={}. customer not found", id),
+        }),
+    }
+}
+
+// like businesses, customers who have orders can't be deleted
+#[ic_cdk::update]
+fn delete_customer(id: CustomerId) -> Result<Customer, Error> {
+    match _get_customer(&id) {
+        Some(customer) => {
+            ensure_role(customer.business_id, Role::Manager)?;
+            let order_count = _list_order_ids_by_customer(id).len();
+            if order_count > 0 {
+                return Err(Error::Conflict {
+                    msg: format!(
+                        "couldn't delete a customer with id={}. they still have {} order(s)",
+                        id, order_count
+                    ),
+                });
+            }
+            do_remove_customer(&customer);
+            audit("delete_customer", Some(&customer), None);
+            Ok(customer)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't delete a customer with id={}. customer not found.", id),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn list_orders_by_customer(
+    customer_id: CustomerId,
+    cursor: Option<OrderId>,
+    limit: u32,
+) -> Result<Page<Order, OrderId>, Error> {
+    let customer = ensure_customer_exists(customer_id)?;
+    ensure_customer_access(&customer)?;
+    let page_size = page_size(limit)?;
+    let mut ids: Vec<OrderId> = ORDERS_BY_CUSTOMER.with(|index| {
+        index
+            .borrow()
+            .range((customer_id, cursor.unwrap_or_default())..)
+            .take_while(|((id, _), _)| *id == customer_id)
+            .take(page_size + 1)
+            .map(|((_, order_id), _)| order_id)
+            .collect()
+    });
+    let next_cursor = if ids.len() > page_size {
+        ids.pop()
+    } else {
+        None
+    };
+    Ok(Page {
+        items: ids.iter().filter_map(_get_order).collect(),
+        next_cursor,
+    })
+}
+
+// cancelled orders aren't counted; paid orders count towards the spend less what was refunded
+// on approved returns, and fully refunded orders don't count towards it at all
+#[ic_cdk::query]
+fn get_customer_summary(customer_id: CustomerId) -> Result<CustomerSummary, Error> {
+    let customer = ensure_customer_exists(customer_id)?;
+    ensure_customer_access(&customer)?;
+    let mut summary = CustomerSummary {
+        customer_id,
+        order_count: 0,
+        lifetime_spend: Vec::new(),
+        last_order_at: None,
+    };
+    for header in _list_order_ids_by_customer(customer_id)
+        .into_iter()
+        .filter_map(_get_order_header)
+    {
+        if header.status == OrderStatus::Cancelled {
+            continue;
+        }
+        summary.order_count += 1;
+        summary.last_order_at = summary.last_order_at.max(Some(header.created_at));
+        if !matches!(
+            header.status,
+            OrderStatus::Paid
+                | OrderStatus::Shipped
+                | OrderStatus::Delivered
+                | OrderStatus::PartiallyRefunded
+        ) {
+            continue;
+        }
+        let mut spent = header.total_price;
+        for rma in _list_returns_by_order(header.id) {
+            if rma.status == ReturnStatus::Approved {
+                spent.amount = spent.amount.saturating_sub(rma.refund_amount.amount);
+            }
+        }
+        match summary
+            .lifetime_spend
+            .iter_mut()
+            .find(|spend| spend.same_currency(&spent))
+        {
+            Some(spend) => *spend = spend.checked_add(&spent)?,
+            None => summary.lifetime_spend.push(spent),
+        }
+    }
+    Ok(summary)
+}
+
+fn do_insert_customer(customer: &Customer) {
+    CUSTOMER_STORAGE.with(|service| service.borrow_mut().insert(customer.id, customer.clone()));
+    CUSTOMERS_BY_BUSINESS
+        .with(|index| index.borrow_mut().insert((customer.business_id, customer.id), ()));
+}
+
+fn do_remove_customer(customer: &Customer) {
+    CUSTOMER_STORAGE.with(|service| service.borrow_mut().remove(&customer.id));
+    CUSTOMERS_BY_BUSINESS
+        .with(|index| index.borrow_mut().remove(&(customer.business_id, customer.id)));
+}
+
+fn ensure_customer_exists(id: CustomerId) -> Result<Customer, Error> {
+    _get_customer(&id).ok_or_else(|| Error::NotFound {
+        msg: format!("a customer with id={} not found", id),
+    })
+}
+
+// a customer with a linked principal can read their own records; anyone else needs a role
+fn ensure_customer_access(customer: &Customer) -> Result<(), Error> {
+    if customer.principal == Some(caller()) {
+        return Ok(());
+    }
+    ensure_role(customer.business_id, Role::Viewer).map(|_| ())
+}
+
+// an order can only name a customer of its own business
+fn ensure_customer_in_business(id: CustomerId, business_id: BusinessId) -> Result<(), Error> {
+    match _get_customer(&id) {
+        Some(customer) if customer.business_id == business_id => Ok(()),
+        _ => Err(Error::NotFound {
+            msg: format!(
+                "a customer with id={} not found in the business with id={}",
+                id, business_id
+            ),
+        }),
+    }
+}
+
+fn _get_customer(id: &CustomerId) -> Option<Customer> {
+    CUSTOMER_STORAGE.with(|service| service.borrow().get(id))
+}
+
+fn _list_customer_ids_by_business(business_id: BusinessId) -> Vec<CustomerId> {
+    CUSTOMERS_BY_BUSINESS.with(|index| {
+        index
+            .borrow()
+            .range((business_id, CustomerId::default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+            .map(|((_, customer_id), _)| customer_id)
+            .collect()
+    })
+}
+
+fn _list_order_ids_by_customer(customer_id: CustomerId) -> Vec<OrderId> {
+    ORDERS_BY_CUSTOMER.with(|index| {
+        index
+            .borrow()
+            .range((customer_id, OrderId::default())..)
+            .take_while(|((id, _), _)| *id == customer_id)
+            .map(|((_, order_id), _)| order_id)
+            .collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_coupon(id: CouponId) -> Result<Coupon, Error> {
+    match _get_coupon(&id) {
+        Some(coupon) => {
+            ensure_role(coupon.business_id, Role::Viewer)?;
+            Ok(coupon)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("a coupon with id={} not found", id),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn list_coupons(business_id: BusinessId) -> Result<Vec<Coupon>, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    Ok(_list_coupon_ids_by_business(business_id)
+        .iter()
+        .filter_map(_get_coupon)
+        .collect())
+}
+
+#[ic_cdk::update]
+fn create_coupon(payload: CouponPayload) -> Result<Coupon, Error> {
+    ensure_business_exists(payload.business_id)?;
+    ensure_role(payload.business_id, Role::Manager)?;
+    payload.validate()?;
+    let code = payload.code.to_uppercase();
+    ensure_coupon_code_free(payload.business_id, &code, None)?;
+    let mut coupon = Coupon {
+        id: CouponId::default(),
+        business_id: payload.business_id,
+        code,
+        discount: payload.discount,
+        valid_from: payload.valid_from,
+        valid_until: payload.valid_until,
+        max_redemptions: payload.max_redemptions,
+        max_redemptions_per_customer: payload.max_redemptions_per_customer,
+        min_order_value: payload.min_order_value,
+        redemption_count: 0,
+        created_at: time(),
+        updated_at: None,
+    };
+    ensure_fits(&coupon)?;
+    coupon.id = next_id(&COUPON_ID_COUNTER)?;
+    do_insert_coupon(&coupon);
+    audit("create_coupon", None, Some(&coupon));
+    Ok(coupon)
+}
+
+// the redemptions counted so far are kept, and a coupon can't move to another business
+#[ic_cdk::update]
+fn update_coupon(id: CouponId, payload: CouponPayload) -> Result<Coupon, Error> {
+    match _get_coupon(&id) {
+        Some(coupon) => {
+            ensure_role(coupon.business_id, Role::Manager)?;
+            if payload.business_id != coupon.business_id {
+                return Err(Error::invalid_field(
+                    "business_id",
+                    "a coupon can't move to another business",
+                ));
+            }
+            payload.validate()?;
+            let code = payload.code.to_uppercase();
+            ensure_coupon_code_free(coupon.business_id, &code, Some(id))?;
+            let updated = Coupon {
+                code,
+                discount: payload.discount,
+                valid_from: payload.valid_from,
+                valid_until: payload.valid_until,
+                max_redemptions: payload.max_redemptions,
+                max_redemptions_per_customer: payload.max_redemptions_per_customer,
+                min_order_value: payload.min_order_value,
+                updated_at: Some(time()),
+                ..coupon.clone()
+            };
+            ensure_fits(&updated)?;
+            COUPONS_BY_CODE.with(|index| {
+                index
+                    .borrow_mut()
+                    .remove(&(coupon.business_id, StorableCode(coupon.code.clone())))
+            });
+            do_insert_coupon(&updated);
+            audit("update_coupon", Some(&coupon), Some(&updated));
+            Ok(updated)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't update a coupon with id={}. coupon not found", id),
+        }),
+    }
+}
+
+// orders that redeemed the coupon keep their discount line
+#[ic_cdk::update]
+fn delete_coupon(id: CouponId) -> Result<Coupon, Error> {
+    match _get_coupon(&id) {
+        Some(coupon) => {
+            ensure_role(coupon.business_id, Role::Manager)?;
+            do_remove_coupon(&coupon);
+            audit("delete_coupon", Some(&coupon), None);
+            Ok(coupon)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't delete a coupon with id={}. coupon not found.", id),
+        }),
+    }
+}
+
+fn do_insert_coupon(coupon: &Coupon) {
+    COUPON_STORAGE.with(|service| service.borrow_mut().insert(coupon.id, coupon.clone()));
+    COUPONS_BY_CODE.with(|index| {
+        index
+            .borrow_mut()
+            .insert((coupon.business_id, StorableCode(coupon.code.clone())), coupon.id)
+    });
+}
+
+fn do_remove_coupon(coupon: &Coupon) {
+    COUPON_STORAGE.with(|service| service.borrow_mut().remove(&coupon.id));
+    COUPONS_BY_CODE.with(|index| {
+        index
+            .borrow_mut()
+            .remove(&(coupon.business_id, StorableCode(coupon.code.clone())))
+    });
+    let customers: Vec<CustomerId> = COUPON_REDEMPTIONS.with(|service| {
+        service
+            .borrow()
+            .range((coupon.id, CustomerId::default())..)
+            .take_while(|((id, _), _)| *id == coupon.id)
+            .map(|((_, customer), _)| customer)
+            .collect()
+    });
+    COUPON_REDEMPTIONS.with(|service| {
+        let mut service = service.borrow_mut();
+        for customer in customers {
+            service.remove(&(coupon.id, customer));
+        }
+    });
+}
+
+fn ensure_coupon_code_free(
+    business_id: BusinessId,
+    code: &str,
+    coupon_id: Option<CouponId>,
+) -> Result<(), Error> {
+    match COUPONS_BY_CODE
+        .with(|index| index.borrow().get(&(business_id, StorableCode(code.to_string()))))
+    {
+        Some(existing) if Some(existing) != coupon_id => Err(Error::Conflict {
+            msg: format!(
+                "the business with id={} already has a coupon with code {}",
+                business_id, code
+            ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn find_coupon(business_id: BusinessId, code: &str) -> Result<Coupon, Error> {
+    COUPONS_BY_CODE
+        .with(|index| {
+            index
+                .borrow()
+                .get(&(business_id, StorableCode(code.to_uppercase())))
+        })
+        .and_then(|id| _get_coupon(&id))
+        .ok_or_else(|| Error::NotFound {
+            msg: format!(
+                "a coupon with code {} not found in the business with id={}",
+                code, business_id
+            ),
+        })
+}
+
+// checks the validity window and the usage limits; the minimum order value is checked when the
+// discount is computed
+fn ensure_coupon_redeemable(
+    coupon: &Coupon,
+    customer_id: Option<CustomerId>,
+    now: u64,
+) -> Result<(), Error> {
+    if now < coupon.valid_from {
+        return Err(Error::InvalidState {
+            msg: format!("coupon {} isn't valid yet", coupon.code),
+        });
+    }
+    if coupon.valid_until.is_some_and(|valid_until| now > valid_until) {
+        return Err(Error::InvalidState {
+            msg: format!("coupon {} has expired", coupon.code),
+        });
+    }
+    if coupon
+        .max_redemptions
+        .is_some_and(|max| coupon.redemption_count >= max)
+    {
+        return Err(Error::LimitExceeded {
+            msg: format!("coupon {} has been used up", coupon.code),
+        });
+    }
+    if let Some(max) = coupon.max_redemptions_per_customer {
+        let Some(customer_id) = customer_id else {
+            return Err(Error::invalid_field(
+                "customer_id",
+                &format!(
+                    "is required for coupon {}, which is limited per customer",
+                    coupon.code
+                ),
+            ));
+        };
+        if _get_redemption_count(coupon.id, customer_id) >= max {
+            return Err(Error::LimitExceeded {
+                msg: format!(
+                    "the customer with id={} has already used coupon {} {} time(s)",
+                    customer_id, coupon.code, max
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+// the discount is taken off the line totals and never exceeds them; percentages are rounded
+// half up to the smallest currency unit
+fn compute_discount(coupon: &Coupon, line_totals: &Money) -> Result<DiscountLine, Error> {
+    if let Some(min) = &coupon.min_order_value {
+        if !min.same_currency(line_totals) {
+            return Err(Error::CurrencyMismatch {
+                msg: format!(
+                    "coupon {} needs an order in {} but the order is in {}",
+                    coupon.code, min.currency, line_totals.currency
+                ),
+            });
+        }
+        if line_totals.amount < min.amount {
+            return Err(Error::invalid_field(
+                "coupon_code",
+                &format!("coupon {} needs an order of at least {}", coupon.code, min),
+            ));
+        }
+    }
+    let amount = match &coupon.discount {
+        Discount::Percentage(rate) => {
+            mul_div_round(line_totals.amount, *rate as u64, RATE_SCALE)?
+        }
+        Discount::Fixed(amount) => {
+            if !amount.same_currency(line_totals) {
+                return Err(Error::CurrencyMismatch {
+                    msg: format!(
+                        "coupon {} takes off {} but the order is in {}",
+                        coupon.code, amount, line_totals.currency
+                    ),
+                });
+            }
+            amount.amount
+        }
+    };
+    Ok(DiscountLine {
+        coupon_id: coupon.id,
+        code: coupon.code.clone(),
+        discount: coupon.discount.clone(),
+        amount: Money {
+            amount: amount.min(line_totals.amount),
+            ..line_totals.clone()
+        },
+    })
+}
+
+// adds `delta` redemptions, or takes them back when negative
+fn do_count_redemption(
+    method: &str,
+    before: Coupon,
+    customer_id: Option<CustomerId>,
+    delta: i64,
+) {
+    let coupon = Coupon {
+        redemption_count: (before.redemption_count as i64 + delta).max(0) as u32,
+        ..before.clone()
+    };
+    COUPON_STORAGE.with(|service| service.borrow_mut().insert(coupon.id, coupon.clone()));
+    audit(method, Some(&before), Some(&coupon));
+    if let Some(customer_id) = customer_id {
+        let count = (_get_redemption_count(coupon.id, customer_id) as i64 + delta).max(0) as u32;
+        COUPON_REDEMPTIONS.with(|service| {
+            let mut service = service.borrow_mut();
+            let key = (coupon.id, customer_id);
+            if count == 0 {
+                service.remove(&key);
+            } else {
+                service.insert(key, count);
+            }
+        });
+    }
+}
+
+// gives back the redemption of a pending order that is changed or deleted
+fn release_redemption(method: &str, order: &Order) {
+    if let Some(coupon) = order
+        .discount
+        .as_ref()
+        .and_then(|discount| _get_coupon(&discount.coupon_id))
+    {
+        do_count_redemption(method, coupon, order.customer_id, -1);
+    }
+}
+
+fn _get_coupon(id: &CouponId) -> Option<Coupon> {
+    COUPON_STORAGE.with(|service| service.borrow().get(id))
+}
+
+fn _get_redemption_count(coupon_id: CouponId, customer_id: CustomerId) -> u32 {
+    COUPON_REDEMPTIONS.with(|service| {
+        service
+            .borrow()
+            .get(&(coupon_id, customer_id))
+            .unwrap_or(0)
+    })
+}
+
+fn _list_coupon_ids_by_business(business_id: BusinessId) -> Vec<CouponId> {
+    COUPONS_BY_CODE.with(|index| {
+        index
+            .borrow()
+            .range((business_id, StorableCode::default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+            .map(|(_, coupon_id)| coupon_id)
+            .collect()
+    })
+}
+
+// replaces every tax rate of the business
+#[ic_cdk::update]
+fn set_tax_rates(business_id: BusinessId, rates: Vec<TaxRate>) -> Result<Vec<TaxRate>, Error> {
+    ensure_business_exists(business_id)?;
+    ensure_role(business_id, Role::Manager)?;
+    rates.validate()?;
+    for rate in rates.iter() {
+        ensure_fits(rate)?;
+    }
+    let before = TaxRateTable {
+        business_id,
+        rates: _list_tax_rates(business_id),
+    };
+    do_set_tax_rates(business_id, &rates);
+    let after = TaxRateTable {
+        business_id,
+        rates,
+    };
+    audit("set_tax_rates", Some(&before), Some(&after));
+    Ok(after.rates)
+}
+
+#[ic_cdk::query]
+fn list_tax_rates(business_id: BusinessId) -> Result<Vec<TaxRate>, Error> {
+    ensure_business_exists(business_id)?;
+    ensure_role(business_id, Role::Viewer)?;
+    Ok(_list_tax_rates(business_id))
+}
+
+fn do_set_tax_rates(business_id: BusinessId, rates: &[TaxRate]) {
+    let previous_count = _list_tax_rates(business_id).len() as u32;
+    TAX_RATES.with(|service| {
+        let mut service = service.borrow_mut();
+        for (position, rate) in rates.iter().enumerate() {
+            service.insert((business_id, position as u32), rate.clone());
+        }
+        for position in rates.len() as u32..previous_count {
+            service.remove(&(business_id, position));
+        }
+    });
+}
+
+fn _list_tax_rates(business_id: BusinessId) -> Vec<TaxRate> {
+    TAX_RATES.with(|service| {
+        service
+            .borrow()
+            .range((business_id, 0)..=(business_id, u32::MAX))
+            .map(|(_, rate)| rate)
+            .collect()
+    })
+}
+
+#[ic_cdk::query]
+fn get_product(id: ProductId) -> Result<Product, Error> {
+    match _get_product(&id) {
+        Some(product) => Ok(product),
+        None => Err(Error::NotFound {
+            msg: format!("a product with id={} not found", id),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn list_products(
+    cursor: Option<ProductId>,
+    limit: u32,
+) -> Result<Page<Product, ProductId>, Error> {
+    PRODUCT_STORAGE.with(|service| paginate(&service.borrow(), cursor, limit))
+}
+
+#[ic_cdk::update]
+fn add_product(product: ProductPayload) -> Result<Product, Error> {
+    let business = ensure_business_exists(product.business_id)?;
+    ensure_role(product.business_id, Role::Manager)?;
+    product.validate()?;
+    product.validate_currencies(&business.base_currency)?;
+    let mut product = Product {
+        id: ProductId::default(),
+        business_id: product.business_id,
+        name: product.name,
+        description: product.description,
+        price: product.price,
+        prices: product.prices,
+        tax_category: product.tax_category,
+        stock_quantity: 0,
+        created_at: time(),
+        updated_at: None,
+    };
+    ensure_fits(&product)?;
+    product.id = next_id(&PRODUCT_ID_COUNTER)?;
+    do_insert_product(&product);
+    index_product(&product);
+    audit("add_product", None, Some(&product));
+    Ok(product)
+}
+
+#[ic_cdk::update]
+fn update_product(id: ProductId, payload: ProductPayload) -> Result<Product, Error> {
+    match PRODUCT_STORAGE.with(|service| service.borrow().get(&id)) {
+        Some(product) => {
+            ensure_role(product.business_id, Role::Manager)?;
+            let business = ensure_business_exists(payload.business_id)?;
+            if payload.business_id != product.business_id {
+                // moving a product to another business requires managing both of them
+                ensure_role(payload.business_id, Role::Manager)?;
+            }
+            payload.validate()?;
+            payload.validate_currencies(&business.base_currency)?;
+            let updated = Product {
+                business_id: payload.business_id,
+                name: payload.name,
+                description: payload.description,
+                price: payload.price,
+                prices: payload.prices,
+                tax_category: payload.tax_category,
+                updated_at: Some(time()),
+                ..product.clone()
+            };
+            ensure_fits(&updated)?;
+            PRODUCTS_BY_BUSINESS
+                .with(|index| index.borrow_mut().remove(&(product.business_id, product.id)));
+            unindex_product(&product);
+            do_insert_product(&updated);
+            index_product(&updated);
+            audit("update_product", Some(&product), Some(&updated));
+            Ok(updated)
+        }
+        None => Err(Error::NotFound {
+            msg: format!(
+                "couldn't update a product with id={}. product not found",
+                id
+            ),
+        }),
+    }
+}
+
+fn do_insert_product(product: &Product) {
+    PRODUCT_STORAGE
+        .with(|service| service.borrow_mut().insert(product.id, product.clone()));
+    PRODUCTS_BY_BUSINESS
+        .with(|index| index.borrow_mut().insert((product.business_id, product.id), ()));
+}
+
+fn do_remove_product(id: ProductId) -> Option<Product> {
+    let product = PRODUCT_STORAGE.with(|service| service.borrow_mut().remove(&id))?;
+    PRODUCTS_BY_BUSINESS
+        .with(|index| index.borrow_mut().remove(&(product.business_id, product.id)));
+    unindex_product(&product);
+    Some(product)
+}
+
+#[ic_cdk::query]
+fn list_products_by_business(
+    business_id: BusinessId,
+    cursor: Option<ProductId>,
+    limit: u32,
+) -> Result<Page<Product, ProductId>, Error> {
+    ensure_business_exists(business_id)?;
+    let page_size = page_size(limit)?;
+    let mut ids: Vec<ProductId> = PRODUCTS_BY_BUSINESS.with(|index| {
+        index
+            .borrow()
+            .range((business_id, cursor.unwrap_or_default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+            .take(page_size + 1)
+            .map(|((_, product_id), _)| product_id)
+            .collect()
+    });
+    let next_cursor = if ids.len() > page_size {
+        ids.pop()
+    } else {
+        None
+    };
+    Ok(Page {
+        items: ids.iter().filter_map(_get_product).collect(),
+        next_cursor,
+    })
+}
+
+#[ic_cdk::update]
+fn delete_product(id: ProductId) -> Result<Product, Error> {
+    match _get_product(&id) {
+        Some(product) => {
+            ensure_role(product.business_id, Role::Manager)?;
+            do_remove_product(id);
+            audit("delete_product", Some(&product), None);
+            Ok(product)
+        }
+        None => Err(Error::NotFound {
+            msg: format!(
+                "couldn't delete a product with id={}. product not found.",
+                id
+            ),
+        }),
+    }
+}
+
+#[ic_cdk::update]
+fn restock_product(id: ProductId, quantity: u64) -> Result<Product, Error> {
+    do_adjust_stock("restock_product", id, quantity as i128)
+}
+
+// a positive delta adds units, a negative one writes units off (e.g. breakage or a stock count)
+#[ic_cdk::update]
+fn adjust_stock(id: ProductId, delta: i64) -> Result<Product, Error> {
+    do_adjust_stock("adjust_stock", id, delta as i128)
+}
+
+fn do_adjust_stock(method: &str, id: ProductId, delta: i128) -> Result<Product, Error> {
+    match _get_product(&id) {
+        Some(before) => {
+            let mut product = before.clone();
+            ensure_role(product.business_id, Role::Manager)?;
+            let adjusted = product.stock_quantity as i128 + delta;
+            if adjusted < 0 {
+                return Err(Error::InsufficientStock {
+                    msg: format!(
+                        "couldn't remove {} unit(s) from a product with id={}",
+                        delta.unsigned_abs(),
+                        id
+                    ),
+                    items: vec![StockShortage {
+                        product_id: id,
+                        requested: delta.unsigned_abs() as u64,
+                        available: product.stock_quantity,
+                    }],
+                });
+            }
+            if adjusted > u64::MAX as i128 {
+                return Err(Error::LimitExceeded {
+                    msg: format!("the stock of a product with id={} would overflow", id),
+                });
+            }
+            product.stock_quantity = adjusted as u64;
+            product.updated_at = Some(time());
+            do_insert_product(&product);
+            audit(method, Some(&before), Some(&product));
+            Ok(product)
+        }
+        None => Err(Error::NotFound {
+            msg: format!(
+                "couldn't adjust the stock of a product with id={}. product not found",
+                id
+            ),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn get_order(id: OrderId) -> Result<Order, Error> {
+    match _get_order(&id) {
+        Some(order) => {
+            ensure_role(order.business_id, Role::Viewer)?;
+            Ok(order)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("an order with id={} not found", id),
+        }),
+    }
+}
+
+// orders are private to their business, so they are paged through the business's order index
+#[ic_cdk::query]
+fn list_orders(
+    business_id: BusinessId,
+    cursor: Option<OrderId>,
+    limit: u32,
+) -> Result<Page<Order, OrderId>, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    let page_size = page_size(limit)?;
+    let mut ids: Vec<OrderId> = ORDERS_BY_BUSINESS.with(|index| {
+        index
+            .borrow()
+            .range((business_id, cursor.unwrap_or_default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+            .take(page_size + 1)
+            .map(|((_, order_id), _)| order_id)
+            .collect()
+    });
+    let next_cursor = if ids.len() > page_size {
+        ids.pop()
+    } else {
+        None
+    };
+    Ok(Page {
+        items: ids.iter().filter_map(_get_order).collect(),
+        next_cursor,
+    })
+}
+
+#[ic_cdk::update]
+fn create_order(order_payload: OrderPayload) -> Result<Order, Error> {
+    let business = ensure_business_exists(order_payload.business_id)?;
+    ensure_role(order_payload.business_id, Role::Cashier)?;
+    order_payload.validate()?;
+
+    if let Some(customer_id) = order_payload.customer_id {
+        ensure_customer_in_business(customer_id, business.id)?;
+    }
+    let created_at = time();
+    let coupon = match &order_payload.coupon_code {
+        Some(code) => Some(find_coupon(business.id, code)?),
+        None => None,
+    };
+    if let Some(coupon) = &coupon {
+        ensure_coupon_redeemable(coupon, order_payload.customer_id, created_at)?;
+    }
+    let pricing = price_order_lines(&business, &order_payload, coupon.as_ref())?;
+    let mut order = Order {
+        id: OrderId::default(),
+        business_id: order_payload.business_id,
+        lines: pricing.lines,
+        customer_id: order_payload.customer_id,
+        discount: pricing.discount,
+        subtotal: pricing.subtotal,
+        tax_lines: pricing.tax_lines,
+        total_price: pricing.total_price,
+        exchange_rate: pricing.exchange_rate,
+        payment: None,
+        status: OrderStatus::Pending,
+        status_history: vec![StatusChange {
+            status: OrderStatus::Pending,
+            changed_by: caller(),
+            changed_at: created_at,
+        }],
+        created_at,
+        updated_at: None,
+    };
+    ensure_fits(&order.header())?;
+    // the id is only taken once the order is sure to be placed, so rejections leave no gaps
+    let stock = stock_changes(&[], &order.lines)?;
+    order.id = next_id(&ORDER_ID_COUNTER)?;
+    do_change_stock("create_order", &stock);
+
+    if let Some(coupon) = coupon {
+        do_count_redemption("create_order", coupon, order.customer_id, 1);
+    }
+    do_insert_order(&order);
+    do_count_sales(&order, true);
+    audit("create_order", None, Some(&order));
+    Ok(order)
+}
+
+#[ic_cdk::update]
+fn update_order(id: OrderId, order_payload: OrderPayload) -> Result<Order, Error> {
+    match _get_order(&id) {
+        Some(order) => {
+            ensure_role(order.business_id, Role::Cashier)?;
+            ensure_order_pending(&order)?;
+            let business = ensure_business_exists(order_payload.business_id)?;
+            if order_payload.business_id != order.business_id {
+                // moving an order to another business requires serving both of them
+                ensure_role(order_payload.business_id, Role::Cashier)?;
+            }
+            order_payload.validate()?;
+            if let Some(customer_id) = order_payload.customer_id {
+                ensure_customer_in_business(customer_id, business.id)?;
+            }
+            let now = time();
+            let coupon = match &order_payload.coupon_code {
+                Some(code) => Some(find_coupon(business.id, code)?),
+                None => None,
+            };
+            let previous_coupon = order.discount.as_ref().map(|discount| discount.coupon_id);
+            // a redemption the order already holds is kept as it is
+            let keeps_redemption = coupon.as_ref().map(|coupon| coupon.id) == previous_coupon
+                && order_payload.customer_id == order.customer_id;
+            if let Some(coupon) = &coupon {
+                if !keeps_redemption {
+                    ensure_coupon_redeemable(coupon, order_payload.customer_id, now)?;
+                }
+            }
+            let pricing = price_order_lines(&business, &order_payload, coupon.as_ref())?;
+            let updated = Order {
+                business_id: order_payload.business_id,
+                lines: pricing.lines,
+                customer_id: order_payload.customer_id,
+                discount: pricing.discount,
+                subtotal: pricing.subtotal,
+                tax_lines: pricing.tax_lines,
+                total_price: pricing.total_price,
+                exchange_rate: pricing.exchange_rate,
+                updated_at: Some(now),
+                ..order.clone()
+            };
+            ensure_fits(&updated.header())?;
+            apply_stock_changes("update_order", &order.lines, &updated.lines)?;
+
+            if !keeps_redemption {
+                release_redemption("update_order", &order);
+                if let Some(coupon) = coupon {
+                    do_count_redemption("update_order", coupon, updated.customer_id, 1);
+                }
+            }
+            do_count_sales(&order, false);
+            do_unindex_order(&order);
+            do_insert_order(&updated);
+            do_count_sales(&updated, true);
+            audit("update_order", Some(&order), Some(&updated));
+            Ok(updated)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't update an order with id={}. order not found", id),
+        }),
+    }
+}
+
+#[ic_cdk::update]
+fn delete_order(id: OrderId) -> Result<Order, Error> {
+    match _get_order(&id) {
+        Some(order) => {
+            ensure_role(order.business_id, Role::Manager)?;
+            ensure_order_pending(&order)?;
+            apply_stock_changes("delete_order", &order.lines, &[])?;
+            release_redemption("delete_order", &order);
+            do_count_sales(&order, false);
+            do_remove_order(&order);
+            audit("delete_order", Some(&order), None);
+            Ok(order)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't delete an order with id={}. order not found", id),
+        }),
+    }
+}
+
+#[ic_cdk::update]
+fn confirm_order(id: OrderId) -> Result<Order, Error> {
+    transition_order("confirm_order", id, OrderStatus::Confirmed, Role::Cashier)
+}
+
+#[ic_cdk::update]
+fn mark_order_paid(id: OrderId) -> Result<Order, Error> {
+    transition_order("mark_order_paid", id, OrderStatus::Paid, Role::Cashier)
+}
+
+#[ic_cdk::update]
+fn ship_order(id: OrderId) -> Result<Order, Error> {
+    transition_order("ship_order", id, OrderStatus::Shipped, Role::Cashier)
+}
+
+#[ic_cdk::update]
+fn deliver_order(id: OrderId) -> Result<Order, Error> {
+    transition_order("deliver_order", id, OrderStatus::Delivered, Role::Cashier)
+}
+
+#[ic_cdk::update]
+fn cancel_order(id: OrderId) -> Result<Order, Error> {
+    let order = transition_order("cancel_order", id, OrderStatus::Cancelled, Role::Cashier)?;
+    apply_stock_changes("cancel_order", &order.lines, &[])?;
+    do_count_sales(&order, false);
+    Ok(order)
+}
+
+// Books whatever approved returns haven't refunded yet as refunded, for orders marked paid by
+// hand. Nothing is sent from here, so orders paid on a ledger are refunded through returns.
+#[ic_cdk::update]
+fn refund_order(id: OrderId) -> Result<Order, Error> {
+    let before = match _get_order(&id) {
+        Some(order) => order,
+        None => {
+            return Err(Error::NotFound {
+                msg: format!("couldn't refund an order with id={}. order not found", id),
+            })
+        }
+    };
+    ensure_role(before.business_id, Role::Manager)?;
+    ensure_no_payment_in_flight(id)?;
+    if before.payment.is_some() {
+        return Err(Error::InvalidState {
+            msg: format!(
+                "an order with id={} was paid on a ledger; refund it with request_return and \
+                 approve_return so the funds go back",
+                id
+            ),
+        });
+    }
+    let mut refunded = 0u64;
+    for rma in _list_returns_by_order(id) {
+        if rma.status == ReturnStatus::Approved {
+            refunded = refunded.saturating_add(rma.refund_amount.amount);
+        }
+    }
+    let header = before.header();
+    let prepared = prepare_transition(header.clone(), OrderStatus::Refunded)?;
+    let amount = header.total_price.amount.saturating_sub(refunded);
+    let mut entry = refund_entry(&header, amount, None)?;
+    if let Some(entry) = entry.as_mut() {
+        entry.id = next_id(&JOURNAL_ID_COUNTER)?;
+    }
+    let order = apply_transition("refund_order", prepared);
+    if let Some(entry) = entry {
+        do_insert_entry(&entry);
+        audit("refund_order", None, Some(&entry));
+    }
+    audit("refund_order", Some(&before), Some(&order));
+    Ok(order)
+}
+
+fn transition_order(
+    method: &str,
+    id: OrderId,
+    next: OrderStatus,
+    required: Role,
+) -> Result<Order, Error> {
+    match _get_order(&id) {
+        Some(before) => {
+            ensure_role(before.business_id, required)?;
+            ensure_no_payment_in_flight(id)?;
+            let order = do_transition_order(method, before.header(), next)?;
+            audit(method, Some(&before), Some(&order));
+            Ok(order)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't update an order with id={}. order not found", id),
+        }),
+    }
+}
+
+fn ensure_no_payment_in_flight(id: OrderId) -> Result<(), Error> {
+    if PAYMENTS_IN_FLIGHT.with(|orders| orders.borrow().contains(&id)) {
+        return Err(Error::Conflict {
+            msg: format!("a payment for the order with id={} is in progress", id),
+        });
+    }
+    Ok(())
+}
+
+// only the order header is rewritten; the lines stay as they are
+fn do_transition_order(
+    method: &str,
+    header: OrderHeader,
+    next: OrderStatus,
+) -> Result<Order, Error> {
+    let prepared = prepare_transition(header, next)?;
+    Ok(apply_transition(method, prepared))
+}
+
+// a status change with the header, invoices and journal entries it writes already built and
+// checked, so an endpoint that calls a ledger first can find out whether the change would fail
+// before any money moves
+struct PreparedTransition {
+    header: OrderHeader,
+    settled: Vec<(Invoice, Invoice)>,
+    entries: Vec<JournalEntry>,
+}
+
+// the entry ids are taken here; a transition that is never applied leaves a gap in the ids
+fn prepare_transition(
+    mut header: OrderHeader,
+    next: OrderStatus,
+) -> Result<PreparedTransition, Error> {
+    ensure_can_transition(&header, next)?;
+    let changed_at = time();
+    record_status(
+        &mut header,
+        StatusChange {
+            status: next,
+            changed_by: caller(),
+            changed_at,
+        },
+    );
+    ensure_fits(&header)?;
+    let settled = if next == OrderStatus::Paid {
+        settled_invoices(header.id, changed_at)?
+    } else {
+        Vec::new()
+    };
+    let mut entries = order_entries(&header)?;
+    for entry in entries.iter_mut() {
+        entry.id = next_id(&JOURNAL_ID_COUNTER)?;
+    }
+    Ok(PreparedTransition {
+        header,
+        settled,
+        entries,
+    })
+}
+
+fn apply_transition(method: &str, prepared: PreparedTransition) -> Order {
+    let PreparedTransition {
+        header,
+        settled,
+        entries,
+    } = prepared;
+    for entry in entries.iter() {
+        do_insert_entry(entry);
+        audit(method, None, Some(entry));
+    }
+    // an order still in the old layout has to be moved before its header can be saved
+    move_legacy_order(header.id);
+    do_insert_order_header(&header);
+    for (before, invoice) in settled.iter() {
+        do_insert_invoice(invoice);
+        audit(method, Some(before), Some(invoice));
+    }
+    let lines = _get_order_lines(&header);
+    let tax_lines = _get_order_tax_lines(&header);
+    let payment = _get_order_payment(&header.id);
+    Order::from_parts(header, lines, tax_lines, payment)
+}
+
+// the oldest changes after the first are dropped once the history is full
+fn record_status(header: &mut OrderHeader, change: StatusChange) {
+    header.status = change.status;
+    header.updated_at = Some(change.changed_at);
+    header.status_history.push(change);
+    let excess = header.status_history.len().saturating_sub(MAX_STATUS_HISTORY);
+    if excess > 0 {
+        header.status_history.drain(1..1 + excess);
+    }
+}
+
+fn ensure_can_transition(header: &OrderHeader, next: OrderStatus) -> Result<(), Error> {
+    if !header.status.can_transition_to(next) {
+        return Err(Error::InvalidState {
+            msg: format!(
+                "an order with id={} can't move from {:?} to {:?}",
+                header.id, header.status, next
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn ensure_order_exists(id: OrderId) -> Result<Order, Error> {
+    _get_order(&id).ok_or_else(|| Error::NotFound {
+        msg: format!("an order with id={} not found", id),
+    })
+}
+
+// orders can only be edited or removed before anyone has acted on them
+fn ensure_order_pending(order: &Order) -> Result<(), Error> {
+    if order.status != OrderStatus::Pending {
+        return Err(Error::InvalidState {
+            msg: format!(
+                "an order with id={} is {:?} and can no longer be changed",
+                order.id, order.status
+            ),
+        });
+    }
+    Ok(())
+}
+
+// an order's lines priced in the buyer's currency with their taxes, and the rate that
+// converted them if any
+struct Pricing {
+    lines: Vec<OrderLine>,
+    discount: Option<DiscountLine>,
+    subtotal: Money,
+    tax_lines: Vec<TaxLine>,
+    total_price: Money,
+    exchange_rate: Option<ExchangeRate>,
+}
+
+// snapshots the current price of every requested product in the order's currency and returns
+// the lines with the order total. A product's fixed price in that currency is used when it has
+// one; otherwise its base price is converted with the base/order currency rate. Every line of
+// an order must end up in the same currency. A coupon's discount comes off before taxes.
+fn price_order_lines(
+    business: &Business,
+    payload: &OrderPayload,
+    coupon: Option<&Coupon>,
+) -> Result<Pricing, Error> {
+    let base = &business.base_currency;
+    let currency = payload.currency.clone().unwrap_or_else(|| base.code.clone());
+    let mut lines: Vec<OrderLine> = Vec::new();
+    let mut total_price: Option<Money> = None;
+    let mut exchange_rate: Option<ExchangeRate> = None;
+    // line totals per tax category
+    let mut taxable: BTreeMap<String, Money> = BTreeMap::new();
+
+    for line in payload.lines.iter() {
+        match _get_product(&line.product_id) {
+            Some(product) if product.business_id == business.id => {
+                let unit_price = if product.price.currency == currency {
+                    product.price
+                } else if let Some(price) = product.prices.iter().find(|p| p.currency == currency) {
+                    price.clone()
+                } else {
+                    let rate = match exchange_rate.take() {
+                        Some(rate) => rate,
+                        None => _get_exchange_rate(&base.code, &currency).ok_or_else(|| {
+                            Error::NotFound {
+                                msg: format!(
+                                    "no {}/{} exchange rate to price product with id={}",
+                                    base.code, currency, product.id
+                                ),
+                            }
+                        })?,
+                    };
+                    let converted = rate.convert(&product.price)?;
+                    exchange_rate = Some(rate);
+                    converted
+                };
+                if let Some(first) = lines.first() {
+                    if !first.unit_price.same_currency(&unit_price) {
+                        return Err(Error::CurrencyMismatch {
+                            msg: format!(
+                                "product with id={} is priced in {} but product with id={} in {}. \
+                                 an order can only use one currency",
+                                product.id,
+                                unit_price,
+                                first.product_id,
+                                first.unit_price
+                            ),
+                        });
+                    }
+                }
+                let line_total = unit_price.checked_mul(line.quantity as u64)?;
+                total_price = Some(match total_price {
+                    Some(total) => total.checked_add(&line_total)?,
+                    None => line_total.clone(),
+                });
+                if let Some(category) = product.tax_category {
+                    let amount = match taxable.remove(&category) {
+                        Some(amount) => amount.checked_add(&line_total)?,
+                        None => line_total.clone(),
+                    };
+                    taxable.insert(category, amount);
+                }
+                lines.push(OrderLine {
+                    product_id: product.id,
+                    quantity: line.quantity,
+                    unit_price,
+                    line_total,
+                });
+            }
+            _ => {
+                return Err(Error::NotFound {
+                    msg: format!(
+                        "product with id={} not found in the business with id={}",
+                        line.product_id, business.id
+                    ),
+                });
+            }
+        }
+    }
+
+    let Some(line_totals) = total_price else {
+        return Err(Error::invalid_field("lines", "must not be empty"));
+    };
+    let discount = match coupon {
+        Some(coupon) => Some(compute_discount(coupon, &line_totals)?),
+        None => None,
+    };
+    let mut discounted = line_totals.clone();
+    if let Some(discount) = &discount {
+        // the discount is spread over the tax categories in proportion to their line totals
+        if line_totals.amount > 0 {
+            for amount in taxable.values_mut() {
+                let share =
+                    mul_div_round(discount.amount.amount, amount.amount, line_totals.amount)?;
+                amount.amount = amount.amount.saturating_sub(share);
+            }
+        }
+        discounted.amount = discounted.amount.saturating_sub(discount.amount.amount);
+    }
+    let tax_lines = compute_tax_lines(&_list_tax_rates(business.id), &taxable)?;
+    let mut included = Money {
+        amount: 0,
+        ..discounted.clone()
+    };
+    let mut total_price = discounted.clone();
+    for line in tax_lines.iter() {
+        if line.rate.inclusive {
+            included = included.checked_add(&line.tax)?;
+        } else {
+            total_price = total_price.checked_add(&line.tax)?;
+        }
+    }
+    Ok(Pricing {
+        lines,
+        discount,
+        subtotal: Money {
+            amount: discounted.amount.saturating_sub(included.amount),
+            ..discounted
+        },
+        tax_lines,
+        total_price,
+        exchange_rate,
+    })
+}
+
+// Taxes are worked out per tax category on the sum of the category's line totals less its share
+// of any discount, never per line, so the result doesn't depend on how the lines are split:
+//  - the net amount of a category is its line totals divided by one plus the sum of its
+//    inclusive rates, rounded half up to the smallest currency unit
+//  - every rate of the category, inclusive or exclusive, is then applied to that net amount and
+//    rounded half up to the smallest currency unit
+// Categories are taken in alphabetical order and rates in the order the business set them.
+// The subtotal is the discounted line totals less the inclusive taxes, so the subtotal plus the
+// tax lines always adds up to the grand total exactly.
+fn compute_tax_lines(
+    rates: &[TaxRate],
+    taxable: &BTreeMap<String, Money>,
+) -> Result<Vec<TaxLine>, Error> {
+    let mut tax_lines = Vec::new();
+    for (category, gross) in taxable {
+        let applicable: Vec<&TaxRate> = rates.iter().filter(|r| &r.category == category).collect();
+        let inclusive_rate: u64 = applicable
+            .iter()
+            .filter(|r| r.inclusive)
+            .map(|r| r.rate as u64)
+            .sum();
+        let net = Money {
+            amount: mul_div_round(gross.amount, RATE_SCALE, RATE_SCALE + inclusive_rate)?,
+            ..gross.clone()
+        };
+        for rate in applicable {
+            tax_lines.push(TaxLine {
+                rate: rate.clone(),
+                taxable_amount: net.clone(),
+                tax: Money {
+                    amount: mul_div_round(net.amount, rate.rate as u64, RATE_SCALE)?,
+                    ..net.clone()
+                },
+            });
+        }
+    }
+    Ok(tax_lines)
+}
+
+// `amount * numerator / denominator`, rounded half up
+fn mul_div_round(amount: u64, numerator: u64, denominator: u64) -> Result<u64, Error> {
+    let product = amount as u128 * numerator as u128;
+    let denominator = denominator as u128;
+    let rounded = product / denominator + u128::from(product % denominator * 2 >= denominator);
+    rounded.try_into().map_err(|_| Error::LimitExceeded {
+        msg: format!("{} * {} / {} overflows", amount, numerator, denominator),
+    })
+}
+
+// puts the `released` lines back on the shelf and takes the `reserved` ones off it.
+// nothing is written unless every product has enough stock for the net change.
+fn apply_stock_changes(
+    method: &str,
+    released: &[OrderLine],
+    reserved: &[OrderLine],
+) -> Result<(), Error> {
+    let changes = stock_changes(released, reserved)?;
+    do_change_stock(method, &changes);
+    Ok(())
+}
+
+// the products before and after the net change, or the shortages if any product runs short
+fn stock_changes(
+    released: &[OrderLine],
+    reserved: &[OrderLine],
+) -> Result<Vec<(Product, Product)>, Error> {
+    let mut deltas: BTreeMap<ProductId, i128> = BTreeMap::new();
+    for line in released {
+        *deltas.entry(line.product_id).or_default() += line.quantity as i128;
+    }
+    for line in reserved {
+        *deltas.entry(line.product_id).or_default() -= line.quantity as i128;
+    }
+
+    let mut updated = Vec::new();
+    let mut shortages = Vec::new();
+    for (product_id, delta) in deltas {
+        // products deleted since the order was placed have no stock to return
+        let Some(before) = _get_product(&product_id).filter(|_| delta != 0) else {
+            continue;
+        };
+        let adjusted = before.stock_quantity as i128 + delta;
+        if adjusted < 0 {
+            shortages.push(StockShortage {
+                product_id,
+                requested: delta.unsigned_abs() as u64,
+                available: before.stock_quantity,
+            });
+        } else {
+            let product = Product {
+                stock_quantity: adjusted.min(u64::MAX as i128) as u64,
+                ..before.clone()
+            };
+            updated.push((before, product));
+        }
+    }
+
+    if !shortages.is_empty() {
+        return Err(Error::InsufficientStock {
+            msg: format!("{} product(s) don't have enough stock", shortages.len()),
+            items: shortages,
+        });
+    }
+    Ok(updated)
+}
+
+fn do_change_stock(method: &str, changes: &[(Product, Product)]) {
+    for (before, product) in changes {
+        do_insert_product(product);
+        audit(method, Some(before), Some(product));
+    }
+}
+
+fn do_insert_order(order: &Order) {
+    let (previous_count, previous_tax_count) = _get_order_header(order.id)
+        .map(|header| (header.line_count, header.tax_line_count))
+        .unwrap_or((0, 0));
+    ORDER_LINE_STORAGE.with(|service| {
+        let mut service = service.borrow_mut();
+        for (line_no, line) in order.lines.iter().enumerate() {
+            service.insert((order.id, line_no as u32), line.clone());
+        }
+        // drop the tail left over when an order shrinks
+        for line_no in order.lines.len() as u32..previous_count {
+            service.remove(&(order.id, line_no));
+        }
+    });
+    ORDER_TAX_LINE_STORAGE.with(|service| {
+        let mut service = service.borrow_mut();
+        for (line_no, line) in order.tax_lines.iter().enumerate() {
+            service.insert((order.id, line_no as u32), line.clone());
+        }
+        for line_no in order.tax_lines.len() as u32..previous_tax_count {
+            service.remove(&(order.id, line_no));
+        }
+    });
+    LEGACY_ORDER_STORAGE.with(|service| service.borrow_mut().remove(&order.id.raw()));
+    do_insert_order_header(&order.header());
+}
+
+// a buyer not linked to a customer yet is kept however the header was rebuilt, unless the
+// order has been given a customer since
+fn do_insert_order_header(header: &OrderHeader) {
+    let mut header = header.clone();
+    if header.customer_id.is_none() && header.unlinked_buyer.is_none() {
+        header.unlinked_buyer =
+            _get_order_header(header.id).and_then(|stored| stored.unlinked_buyer);
+    }
+    ORDER_STORAGE.with(|service| service.borrow_mut().insert(header.id, header.clone()));
+    ORDERS_BY_BUSINESS
+        .with(|index| index.borrow_mut().insert((header.business_id, header.id), ()));
+    if let Some(customer_id) = header.customer_id {
+        ORDERS_BY_CUSTOMER.with(|index| index.borrow_mut().insert((customer_id, header.id), ()));
+    }
+}
+
+fn do_unindex_order(order: &Order) {
+    ORDERS_BY_BUSINESS
+        .with(|index| index.borrow_mut().remove(&(order.business_id, order.id)));
+    if let Some(customer_id) = order.customer_id {
+        ORDERS_BY_CUSTOMER.with(|index| index.borrow_mut().remove(&(customer_id, order.id)));
+    }
+}
+
+fn do_remove_order(order: &Order) {
+    ORDER_STORAGE.with(|service| service.borrow_mut().remove(&order.id));
+    LEGACY_ORDER_STORAGE.with(|service| service.borrow_mut().remove(&order.id.raw()));
+    ORDER_LINE_STORAGE.with(|service| {
+        let mut service = service.borrow_mut();
+        for line_no in 0..order.lines.len() as u32 {
+            service.remove(&(order.id, line_no));
+        }
+    });
+    ORDER_TAX_LINE_STORAGE.with(|service| {
+        let mut service = service.borrow_mut();
+        for line_no in 0..order.tax_lines.len() as u32 {
+            service.remove(&(order.id, line_no));
+        }
+    });
+    do_unindex_order(order);
+}
+
+fn ensure_controller() -> Result<(), Error> {
+    if !ic_cdk::api::is_controller(&caller()) {
+        return Err(Error::Unauthorized {
+            msg: "only controllers of the canister can do this".to_string(),
+        });
+    }
+    Ok(())
+}
+
+// the caller must hold `required` or a more privileged role on the business
+fn ensure_role(business_id: BusinessId, required: Role) -> Result<Role, Error> {
+    match _get_member(business_id, caller()) {
+        Some(member) if member.role >= required => Ok(member.role),
+        _ => Err(Error::Unauthorized {
+            msg: format!(
+                "caller needs the {:?} role on the business with id={}",
+                required, business_id
+            ),
+        }),
+    }
+}
+
+fn ensure_business_exists(business_id: BusinessId) -> Result<Business, Error> {
+    _get_business(&business_id).ok_or_else(|| Error::NotFound {
+        msg: format!("a business with id={} not found", business_id),
+    })
+}
+
+fn _get_member(business_id: BusinessId, principal: Principal) -> Option<Member> {
+    MEMBER_STORAGE.with(|service| {
+        service
+            .borrow()
+            .get(&(business_id, StorablePrincipal(principal)))
+    })
+}
+
+fn _list_members(business_id: BusinessId) -> Vec<Member> {
+    MEMBER_STORAGE.with(|service| {
+        service
+            .borrow()
+            .range((business_id, StorablePrincipal::default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+            .map(|(_, member)| member)
+            .collect()
+    })
+}
+
+fn _list_product_ids_by_business(business_id: BusinessId) -> Vec<ProductId> {
+    PRODUCTS_BY_BUSINESS.with(|index| {
+        index
+            .borrow()
+            .range((business_id, ProductId::default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+            .map(|((_, product_id), _)| product_id)
+            .collect()
+    })
+}
+
+fn _list_order_ids_by_business(business_id: BusinessId) -> Vec<OrderId> {
+    ORDERS_BY_BUSINESS.with(|index| {
+        index
+            .borrow()
+            .range((business_id, OrderId::default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+            .map(|((_, order_id), _)| order_id)
+            .collect()
+    })
+}
+
+// ledgers are set by the canister's controllers, one per currency
+#[ic_cdk::update]
+fn set_ledger(currency: Currency, ledger: Principal) -> Result<LedgerConfig, Error> {
+    ensure_controller()?;
+    let mut v = Validator::default();
+    v.currency("currency", &currency);
+    v.finish()?;
+    let config = LedgerConfig {
+        currency,
+        ledger,
+        updated_by: caller(),
+        updated_at: time(),
+    };
+    let before = LEDGERS.with(|service| {
+        service
+            .borrow_mut()
+            .insert(StorableCode(config.currency.code.clone()), config.clone())
+    });
+    audit("set_ledger", before.as_ref(), Some(&config));
+    Ok(config)
+}
+
+#[ic_cdk::update]
+fn remove_ledger(currency: String) -> Result<LedgerConfig, Error> {
+    ensure_controller()?;
+    match LEDGERS.with(|service| service.borrow_mut().remove(&StorableCode(currency.clone()))) {
+        Some(config) => {
+            audit("remove_ledger", Some(&config), None);
+            Ok(config)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("no ledger is set for {}", currency),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn list_ledgers() -> Result<Vec<LedgerConfig>, Error> {
+    Ok(LEDGERS.with(|service| service.borrow().iter().map(|(_, config)| config).collect()))
+}
+
+// the canister's account on every ledger where a business's payments are collected
+#[ic_cdk::query]
+fn get_business_account(business_id: BusinessId) -> Result<Account, Error> {
+    ensure_business_exists(business_id)?;
+    Ok(business_account(business_id))
+}
+
+// Pulls the order total from the caller's default account into the business's account with
+// `icrc2_transfer_from` on the ledger of the order's currency, so the caller must first approve
+// this canister for the total plus the ledger fee. Only a confirmed order can be paid, and it
+// can't change status while the ledger call is in flight.
+#[ic_cdk::update]
+async fn pay_order(id: OrderId) -> Result<Order, Error> {
+    let before = match _get_order(&id) {
+        Some(order) => order,
+        None => {
+            return Err(Error::NotFound {
+                msg: format!("couldn't pay an order with id={}. order not found", id),
+            })
+        }
+    };
+    let header = before.header();
+    ensure_can_transition(&header, OrderStatus::Paid)?;
+    let ledger = ensure_ledger(&header.total_price)?;
+    let _guard = PaymentGuard::acquire(id)?;
+
+    // everything the payment writes is built and checked before the ledger is called, so once
+    // the funds have moved nothing is left that can fail
+    let from = Account {
+        owner: caller(),
+        subaccount: None,
+    };
+    let to = business_account(header.business_id);
+    let mut payment = Payment {
+        method: PaymentMethod::Approval,
+        ledger: ledger.ledger,
+        from: from.clone(),
+        to: to.clone(),
+        overpaid: Money {
+            amount: 0,
+            ..header.total_price.clone()
+        },
+        amount: header.total_price.clone(),
+        // the block index is only known once the ledger answers; ledgers number blocks with u64s
+        block_index: Nat::from(u64::MAX),
+        paid_at: time(),
+    };
+    ensure_fits(&payment)?;
+    let paid = prepare_transition(header.clone(), OrderStatus::Paid)?;
+
+    payment.block_index = icrc2_transfer_from(
+        ledger.ledger,
+        TransferFromArgs {
+            spender_subaccount: None,
+            from,
+            to,
+            amount: Nat::from(header.total_price.amount),
+            fee: None,
+            memo: Some(id.raw().to_be_bytes().to_vec()),
+            created_at_time: Some(time()),
+        },
+    )
+    .await?;
+    payment.paid_at = time();
+
+    // the guard kept the order from changing while the ledger was called
+    ORDER_PAYMENTS.with(|service| service.borrow_mut().insert(id, payment));
+    let order = apply_transition("pay_order", paid);
+    audit("pay_order", Some(&before), Some(&order));
+    Ok(order)
+}
+
+#[ic_cdk::query]
+fn get_return(id: ReturnId) -> Result<ReturnAuthorization, Error> {
+    match _get_return(&id) {
+        Some(rma) => {
+            ensure_role(rma.business_id, Role::Viewer)?;
+            Ok(rma)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("a return with id={} not found", id),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn list_returns_by_order(order_id: OrderId) -> Result<Vec<ReturnAuthorization>, Error> {
+    match _get_order_header(order_id) {
+        Some(header) => {
+            ensure_role(header.business_id, Role::Viewer)?;
+            Ok(_list_returns_by_order(order_id))
+        }
+        None => Err(Error::NotFound {
+            msg: format!("an order with id={} not found", order_id),
+        }),
+    }
+}
+
+// Opens a return for some or all of the units on a paid order's lines. The refund defaults to the
+// returned units' share of the order total, so discounts and taxes are given back in proportion;
+// a smaller amount can be asked for instead, e.g. to keep a restocking fee.
+#[ic_cdk::update]
+fn request_return(payload: ReturnPayload) -> Result<ReturnAuthorization, Error> {
+    let order = ensure_order_exists(payload.order_id)?;
+    ensure_role(order.business_id, Role::Cashier)?;
+    payload.validate()?;
+    ensure_returnable(&order)?;
+    if payload.refund_to.is_none()
+        && order
+            .payment
+            .as_ref()
+            .is_some_and(|payment| payment.method == PaymentMethod::Deposit)
+    {
+        return Err(Error::invalid_field(
+            "refund_to",
+            "is required for an order paid by deposit",
+        ));
+    }
+    let returns = _list_returns_by_order(order.id);
+    let lines = return_lines(&order, &payload.lines, &returns)?;
+    let refund_amount = refund_amount(&order, &lines, &returns, payload.refund_amount)?;
+    let mut rma = ReturnAuthorization {
+        id: ReturnId::default(),
+        order_id: order.id,
+        business_id: order.business_id,
+        lines,
+        refund_amount,
+        refund_to: payload.refund_to,
+        restocked: false,
+        status: ReturnStatus::Requested,
+        refund: None,
+        requested_by: caller(),
+        requested_at: time(),
+        decided_by: None,
+        decided_at: None,
+    };
+    ensure_fits(&rma)?;
+    rma.id = next_id(&RETURN_ID_COUNTER)?;
+    do_insert_return(&rma);
+    audit("request_return", None, Some(&rma));
+    Ok(rma)
+}
+
+// Refunds the return on the ledger the order was paid on, from the business's account, and puts
+// the units back in stock when `restock` is set. The order moves to Refunded once every unit on
+// it has been returned and to PartiallyRefunded before that. Orders marked paid by hand get no
+// ledger refund.
+#[ic_cdk::update]
+async fn approve_return(id: ReturnId, restock: bool) -> Result<ReturnAuthorization, Error> {
+    let rma = match _get_return(&id) {
+        Some(rma) => rma,
+        None => {
+            return Err(Error::NotFound {
+                msg: format!("couldn't approve a return with id={}. return not found", id),
+            })
+        }
+    };
+    ensure_role(rma.business_id, Role::Manager)?;
+    ensure_return_requested(&rma)?;
+    let order = ensure_order_exists(rma.order_id)?;
+    ensure_returnable(&order)?;
+
+    // everything the approval writes is built and checked before any money moves, so once the
+    // refund is sent nothing is left that can fail
+    let mut approved = _list_returns_by_order(order.id);
+    approved.retain(|other| other.status == ReturnStatus::Approved);
+    approved.push(rma.clone());
+    let next = if returned_quantities(&approved) == ordered_quantities(&order) {
+        OrderStatus::Refunded
+    } else {
+        OrderStatus::PartiallyRefunded
+    };
+    let header = order.header();
+    ensure_can_transition(&header, next)?;
+    let decided_at = time();
+    let mut refunded_header = header.clone();
+    record_status(
+        &mut refunded_header,
+        StatusChange {
+            status: next,
+            changed_by: caller(),
+            changed_at: decided_at,
+        },
+    );
+    ensure_fits(&refunded_header)?;
+
+    let refund_to = order.payment.as_ref().filter(|_| rma.refund_amount.amount > 0).map(|payment| {
+        let to = rma.refund_to.clone().unwrap_or_else(|| payment.from.clone());
+        (payment, to)
+    });
+    let mut approved_rma = ReturnAuthorization {
+        restocked: restock,
+        status: ReturnStatus::Approved,
+        refund: None,
+        decided_by: Some(caller()),
+        decided_at: Some(decided_at),
+        ..rma.clone()
+    };
+    // the block index is only known once the ledger answers; ledgers number blocks with u64s
+    ensure_fits(&ReturnAuthorization {
+        refund: refund_to.as_ref().map(|(payment, to)| Refund {
+            ledger: payment.ledger,
+            to: to.clone(),
+            amount: rma.refund_amount.clone(),
+            block_index: Nat::from(u64::MAX),
+            refunded_at: decided_at,
+        }),
+        ..approved_rma.clone()
+    })?;
+    // the entry id is taken up front; a refund the ledger turns down leaves a gap in the ids
+    let mut entry = refund_entry(&header, rma.refund_amount.amount, Some(rma.id))?;
+    if let Some(entry) = entry.as_mut() {
+        entry.id = next_id(&JOURNAL_ID_COUNTER)?;
+    }
+    let _guard = PaymentGuard::acquire(order.id)?;
+
+    if let Some((payment, to)) = refund_to {
+        let block_index = icrc1_transfer(
+            payment.ledger,
+            TransferArg {
+                from_subaccount: payment.to.subaccount.clone(),
+                to: to.clone(),
+                amount: Nat::from(rma.refund_amount.amount),
+                fee: None,
+                memo: Some(id.raw().to_be_bytes().to_vec()),
+                created_at_time: Some(time()),
+            },
+        )
+        .await?;
+        approved_rma.refund = Some(Refund {
+            ledger: payment.ledger,
+            to,
+            amount: rma.refund_amount.clone(),
+            block_index,
+            refunded_at: time(),
+        });
+    }
+
+    do_insert_return(&approved_rma);
+    if restock {
+        restock_returned(&approved_rma.lines);
+    }
+    if let Some(entry) = entry {
+        do_insert_entry(&entry);
+        audit("approve_return", None, Some(&entry));
+    }
+    // the guard kept the order from changing while the ledger was called
+    move_legacy_order(header.id);
+    do_insert_order_header(&refunded_header);
+    let refunded = Order::from_parts(
+        refunded_header,
+        order.lines.clone(),
+        order.tax_lines.clone(),
+        order.payment.clone(),
+    );
+    audit("approve_return", Some(&rma), Some(&approved_rma));
+    audit("approve_return", Some(&order), Some(&refunded));
+    Ok(approved_rma)
+}
+
+// putting units back on the shelf can't run short, so this is safe to run after a refund is out.
+// products deleted since the order was placed have no stock to return.
+fn restock_returned(lines: &[ReturnLine]) {
+    for line in lines {
+        if let Some(before) = _get_product(&line.product_id) {
+            let product = Product {
+                stock_quantity: before.stock_quantity.saturating_add(line.quantity as u64),
+                ..before.clone()
+            };
+            do_insert_product(&product);
+            audit("approve_return", Some(&before), Some(&product));
+        }
     }
 }
 
 #[ic_cdk::update]
-fn add_product(product: ProductPayload) -> Option<Product> {
-    let id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("cannot increment id counter");
-    let product = Product {
-        id,
-        name: product.name,
-        description: product.description,
-        price: product.price,
+fn reject_return(id: ReturnId) -> Result<ReturnAuthorization, Error> {
+    match _get_return(&id) {
+        Some(rma) => {
+            ensure_role(rma.business_id, Role::Manager)?;
+            ensure_return_requested(&rma)?;
+            // an approval of the same order may be waiting on the ledger
+            let _guard = PaymentGuard::acquire(rma.order_id)?;
+            let rejected = ReturnAuthorization {
+                status: ReturnStatus::Rejected,
+                decided_by: Some(caller()),
+                decided_at: Some(time()),
+                ..rma.clone()
+            };
+            do_insert_return(&rejected);
+            audit("reject_return", Some(&rma), Some(&rejected));
+            Ok(rejected)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't reject a return with id={}. return not found", id),
+        }),
+    }
+}
+
+fn ensure_returnable(order: &Order) -> Result<(), Error> {
+    if !matches!(
+        order.status,
+        OrderStatus::Paid
+            | OrderStatus::Shipped
+            | OrderStatus::Delivered
+            | OrderStatus::PartiallyRefunded
+    ) {
+        return Err(Error::InvalidState {
+            msg: format!(
+                "an order with id={} is {:?}; only paid orders can be returned",
+                order.id, order.status
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn ensure_return_requested(rma: &ReturnAuthorization) -> Result<(), Error> {
+    if rma.status != ReturnStatus::Requested {
+        return Err(Error::InvalidState {
+            msg: format!("a return with id={} was already {:?}", rma.id, rma.status),
+        });
+    }
+    Ok(())
+}
+
+// checks the requested units against what is left of each order line once the returns that
+// aren't rejected are taken off
+fn return_lines(
+    order: &Order,
+    requested: &[ReturnLinePayload],
+    returns: &[ReturnAuthorization],
+) -> Result<Vec<ReturnLine>, Error> {
+    let returned = returned_quantities(returns);
+    let mut v = Validator::default();
+    let mut lines = Vec::new();
+    let mut seen = BTreeSet::new();
+    for (i, line) in requested.iter().enumerate() {
+        let Some(ordered) = order.lines.get(line.line_no as usize) else {
+            v.error(
+                &format!("lines[{}].line_no", i),
+                format!("the order has no line {}", line.line_no),
+            );
+            continue;
+        };
+        if !seen.insert(line.line_no) {
+            v.error(
+                &format!("lines[{}].line_no", i),
+                format!("line {} is listed more than once", line.line_no),
+            );
+            continue;
+        }
+        let left = ordered
+            .quantity
+            .saturating_sub(returned.get(&line.line_no).copied().unwrap_or(0));
+        if line.quantity > left {
+            v.error(
+                &format!("lines[{}].quantity", i),
+                format!("only {} unit(s) of line {} can still be returned", left, line.line_no),
+            );
+            continue;
+        }
+        lines.push(ReturnLine {
+            line_no: line.line_no,
+            product_id: ordered.product_id,
+            quantity: line.quantity,
+            reason: line.reason.clone(),
+        });
+    }
+    v.finish()?;
+    Ok(lines)
+}
+
+// the returned units' share of the order total, or everything not yet refunded when they are the
+// last units of the order
+fn refund_amount(
+    order: &Order,
+    lines: &[ReturnLine],
+    returns: &[ReturnAuthorization],
+    requested: Option<Money>,
+) -> Result<Money, Error> {
+    let open: Vec<&ReturnAuthorization> = returns
+        .iter()
+        .filter(|rma| rma.status != ReturnStatus::Rejected)
+        .collect();
+    let mut committed = 0u64;
+    for rma in open.iter() {
+        committed = committed.saturating_add(rma.refund_amount.amount);
+    }
+    let left = order.total_price.amount.saturating_sub(committed);
+
+    let mut returned = returned_quantities(returns);
+    let mut line_totals = 0u64;
+    let mut returned_totals = 0u64;
+    for line in order.lines.iter() {
+        line_totals = line_totals.saturating_add(line.line_total.amount);
+    }
+    for line in lines {
+        *returned.entry(line.line_no).or_default() += line.quantity;
+        let unit_price = order.lines[line.line_no as usize].unit_price.amount;
+        returned_totals =
+            returned_totals.saturating_add(unit_price.saturating_mul(line.quantity as u64));
+    }
+    let full = if returned == ordered_quantities(order) || line_totals == 0 {
+        left
+    } else {
+        mul_div_round(order.total_price.amount, returned_totals, line_totals)?.min(left)
+    };
+
+    match requested {
+        Some(amount) if !amount.same_currency(&order.total_price) => Err(Error::CurrencyMismatch {
+            msg: format!(
+                "the refund is in {} but the order is in {}",
+                amount.currency, order.total_price.currency
+            ),
+        }),
+        Some(amount) if amount.amount > full => Err(Error::invalid_field(
+            "refund_amount",
+            &format!(
+                "can be at most {}",
+                Money {
+                    amount: full,
+                    ..order.total_price.clone()
+                }
+            ),
+        )),
+        Some(amount) => Ok(amount),
+        None => Ok(Money {
+            amount: full,
+            ..order.total_price.clone()
+        }),
+    }
+}
+
+// units per order line number
+fn ordered_quantities(order: &Order) -> BTreeMap<u32, u32> {
+    order
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(line_no, line)| (line_no as u32, line.quantity))
+        .collect()
+}
+
+// units per order line number that are back or on their way, i.e. on returns that aren't rejected
+fn returned_quantities(returns: &[ReturnAuthorization]) -> BTreeMap<u32, u32> {
+    let mut quantities: BTreeMap<u32, u32> = BTreeMap::new();
+    for rma in returns.iter().filter(|rma| rma.status != ReturnStatus::Rejected) {
+        for line in rma.lines.iter() {
+            *quantities.entry(line.line_no).or_default() += line.quantity;
+        }
+    }
+    quantities
+}
+
+fn do_insert_return(rma: &ReturnAuthorization) {
+    RETURN_STORAGE.with(|service| service.borrow_mut().insert(rma.id, rma.clone()));
+    RETURNS_BY_ORDER.with(|index| index.borrow_mut().insert((rma.order_id, rma.id), ()));
+}
+
+fn _get_return(id: &ReturnId) -> Option<ReturnAuthorization> {
+    RETURN_STORAGE.with(|service| service.borrow().get(id))
+}
+
+fn _list_returns_by_order(order_id: OrderId) -> Vec<ReturnAuthorization> {
+    let ids: Vec<ReturnId> = RETURNS_BY_ORDER.with(|index| {
+        index
+            .borrow()
+            .range((order_id, ReturnId::default())..)
+            .take_while(|((id, _), _)| *id == order_id)
+            .map(|((_, return_id), _)| return_id)
+            .collect()
+    });
+    ids.iter().filter_map(_get_return).collect()
+}
+
+#[ic_cdk::query]
+fn get_invoice(id: InvoiceId) -> Result<Invoice, Error> {
+    match _get_invoice(&id) {
+        Some(invoice) => {
+            ensure_role(invoice.business_id, Role::Viewer)?;
+            Ok(invoice)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("an invoice with id={} not found", id),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn list_invoices(
+    business_id: BusinessId,
+    cursor: Option<InvoiceId>,
+    limit: u32,
+) -> Result<Page<Invoice, InvoiceId>, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    let page_size = page_size(limit)?;
+    let mut ids: Vec<InvoiceId> = INVOICES_BY_BUSINESS.with(|index| {
+        index
+            .borrow()
+            .range((business_id, cursor.unwrap_or_default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+            .take(page_size + 1)
+            .map(|((_, invoice_id), _)| invoice_id)
+            .collect()
+    });
+    let next_cursor = if ids.len() > page_size {
+        ids.pop()
+    } else {
+        None
+    };
+    Ok(Page {
+        items: ids.iter().filter_map(_get_invoice).collect(),
+        next_cursor,
+    })
+}
+
+// drafts an invoice for a confirmed order, copying the billing details and totals; an order can
+// only have one invoice that isn't void
+#[ic_cdk::update]
+fn create_invoice(order_id: OrderId, payment_terms_days: u32) -> Result<Invoice, Error> {
+    let order = ensure_order_exists(order_id)?;
+    ensure_role(order.business_id, Role::Manager)?;
+    let mut v = Validator::default();
+    v.range("payment_terms_days", payment_terms_days, 0, MAX_PAYMENT_TERMS_DAYS);
+    v.finish()?;
+    if matches!(order.status, OrderStatus::Pending | OrderStatus::Cancelled) {
+        return Err(Error::InvalidState {
+            msg: format!(
+                "an order with id={} is {:?}; only confirmed orders can be invoiced",
+                order.id, order.status
+            ),
+        });
+    }
+    if let Some(existing) = _list_invoices_by_order(order.id)
+        .iter()
+        .find(|invoice| invoice.status != InvoiceStatus::Void)
+    {
+        return Err(Error::Conflict {
+            msg: format!(
+                "the order with id={} already has the invoice with id={}",
+                order.id, existing.id
+            ),
+        });
+    }
+    let business = ensure_business_exists(order.business_id)?;
+    let mut tax_total = Money {
+        amount: 0,
+        ..order.total_price.clone()
+    };
+    for line in order.tax_lines.iter() {
+        tax_total = tax_total.checked_add(&line.tax)?;
+    }
+    let mut invoice = Invoice {
+        id: InvoiceId::default(),
+        business_id: order.business_id,
+        order_id: order.id,
+        number: None,
+        seller: BillingDetails {
+            name: business.name,
+            address: business.address,
+            email: None,
+        },
+        buyer: order
+            .customer_id
+            .and_then(|id| _get_customer(&id))
+            .map(|customer| BillingDetails {
+                name: customer.name,
+                address: customer.address,
+                email: Some(customer.email).filter(|email| !email.is_empty()),
+            }),
+        subtotal: order.subtotal,
+        discount: order.discount.map(|discount| discount.amount),
+        tax_total,
+        total: order.total_price,
+        payment_terms_days,
+        status: InvoiceStatus::Draft,
         created_at: time(),
-        updated_at: None,
+        issued_at: None,
+        due_at: None,
+        paid_at: None,
+        voided_at: None,
+    };
+    ensure_fits(&invoice)?;
+    invoice.id = next_id(&INVOICE_ID_COUNTER)?;
+    do_insert_invoice(&invoice);
+    audit("create_invoice", None, Some(&invoice));
+    Ok(invoice)
+}
+
+// gives the invoice the business's next number, so numbers are only used by issued invoices
+// and never skip; an invoice for an order that was already paid is issued as paid
+#[ic_cdk::update]
+fn issue_invoice(id: InvoiceId) -> Result<Invoice, Error> {
+    match _get_invoice(&id) {
+        Some(draft) => {
+            let mut invoice = draft.clone();
+            ensure_role(invoice.business_id, Role::Manager)?;
+            if invoice.status != InvoiceStatus::Draft {
+                return Err(Error::InvalidState {
+                    msg: format!("an invoice with id={} is {:?}, not a draft", id, invoice.status),
+                });
+            }
+            let issued_at = time();
+            let number = INVOICE_NUMBERS
+                .with(|service| service.borrow().get(&invoice.business_id))
+                .unwrap_or(0)
+                .checked_add(1)
+                .ok_or_else(|| Error::LimitExceeded {
+                    msg: "the invoice numbers are exhausted".to_string(),
+                })?;
+            invoice.number = Some(number);
+            invoice.issued_at = Some(issued_at);
+            invoice.due_at = Some(
+                issued_at.saturating_add(invoice.payment_terms_days as u64 * NANOS_PER_DAY),
+            );
+            invoice.status = InvoiceStatus::Issued;
+            if _get_order_header(invoice.order_id).is_some_and(|header| is_paid(header.status)) {
+                invoice.status = InvoiceStatus::Paid;
+                invoice.paid_at = Some(issued_at);
+            }
+            ensure_fits(&invoice)?;
+            INVOICE_NUMBERS
+                .with(|service| service.borrow_mut().insert(invoice.business_id, number));
+            do_insert_invoice(&invoice);
+            audit("issue_invoice", Some(&draft), Some(&invoice));
+            Ok(invoice)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't issue an invoice with id={}. invoice not found", id),
+        }),
+    }
+}
+
+// a void invoice keeps its number, so the numbering stays gapless
+#[ic_cdk::update]
+fn void_invoice(id: InvoiceId) -> Result<Invoice, Error> {
+    match _get_invoice(&id) {
+        Some(before) => {
+            let mut invoice = before.clone();
+            ensure_role(invoice.business_id, Role::Manager)?;
+            if matches!(invoice.status, InvoiceStatus::Paid | InvoiceStatus::Void) {
+                return Err(Error::InvalidState {
+                    msg: format!("an invoice with id={} is {:?}", id, invoice.status),
+                });
+            }
+            invoice.status = InvoiceStatus::Void;
+            invoice.voided_at = Some(time());
+            ensure_fits(&invoice)?;
+            do_insert_invoice(&invoice);
+            audit("void_invoice", Some(&before), Some(&invoice));
+            Ok(invoice)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("couldn't void an invoice with id={}. invoice not found", id),
+        }),
+    }
+}
+
+// the open invoices of an order marked paid, checked so they can be saved along with the order
+fn settled_invoices(order_id: OrderId, paid_at: u64) -> Result<Vec<(Invoice, Invoice)>, Error> {
+    let mut settled = Vec::new();
+    for before in _list_invoices_by_order(order_id) {
+        if matches!(before.status, InvoiceStatus::Issued | InvoiceStatus::Overdue) {
+            let invoice = Invoice {
+                status: InvoiceStatus::Paid,
+                paid_at: Some(paid_at),
+                ..before.clone()
+            };
+            ensure_fits(&invoice)?;
+            settled.push((before, invoice));
+        }
+    }
+    Ok(settled)
+}
+
+// marks up to `limit` issued invoices that are past their due date overdue. an invoice whose
+// order was cancelled or refunded in full won't be paid, so it is voided instead. one that
+// can't be saved never will be, so it is dropped from the due date index rather than left to
+// hold up the invoices behind it; it stays issued and can still be paid or voided by hand.
+fn mark_overdue_invoices(now: u64, limit: usize) {
+    let due: Vec<(u64, InvoiceId)> = INVOICES_BY_DUE.with(|index| {
+        index
+            .borrow()
+            .range(..(now, InvoiceId::default()))
+            .take(limit)
+            .map(|(key, _)| key)
+            .collect()
+    });
+    for (due_at, id) in due {
+        let Some(before) = _get_invoice(&id) else {
+            INVOICES_BY_DUE.with(|index| index.borrow_mut().remove(&(due_at, id)));
+            continue;
+        };
+        let uncollectable = _get_order_header(before.order_id).is_none_or(|header| {
+            matches!(header.status, OrderStatus::Cancelled | OrderStatus::Refunded)
+        });
+        let invoice = if uncollectable {
+            Invoice {
+                status: InvoiceStatus::Void,
+                voided_at: Some(now),
+                ..before.clone()
+            }
+        } else {
+            Invoice {
+                status: InvoiceStatus::Overdue,
+                ..before.clone()
+            }
+        };
+        if ensure_fits(&invoice).is_ok() {
+            do_insert_invoice(&invoice);
+            audit("mark_overdue_invoices", Some(&before), Some(&invoice));
+        } else {
+            INVOICES_BY_DUE.with(|index| index.borrow_mut().remove(&(due_at, id)));
+        }
+    }
+}
+
+fn is_paid(status: OrderStatus) -> bool {
+    matches!(
+        status,
+        OrderStatus::Paid
+            | OrderStatus::Shipped
+            | OrderStatus::Delivered
+            | OrderStatus::PartiallyRefunded
+            | OrderStatus::Refunded
+    )
+}
+
+// only issued invoices are waiting on their due date, so only they are in the due date index
+fn do_insert_invoice(invoice: &Invoice) {
+    INVOICE_STORAGE.with(|service| service.borrow_mut().insert(invoice.id, invoice.clone()));
+    INVOICES_BY_BUSINESS
+        .with(|index| index.borrow_mut().insert((invoice.business_id, invoice.id), ()));
+    INVOICES_BY_ORDER.with(|index| index.borrow_mut().insert((invoice.order_id, invoice.id), ()));
+    if let Some(due_at) = invoice.due_at {
+        INVOICES_BY_DUE.with(|index| {
+            let mut index = index.borrow_mut();
+            if invoice.status == InvoiceStatus::Issued {
+                index.insert((due_at, invoice.id), ());
+            } else {
+                index.remove(&(due_at, invoice.id));
+            }
+        });
+    }
+}
+
+fn _get_invoice(id: &InvoiceId) -> Option<Invoice> {
+    INVOICE_STORAGE.with(|service| service.borrow().get(id))
+}
+
+fn _list_invoices_by_order(order_id: OrderId) -> Vec<Invoice> {
+    let ids: Vec<InvoiceId> = INVOICES_BY_ORDER.with(|index| {
+        index
+            .borrow()
+            .range((order_id, InvoiceId::default())..)
+            .take_while(|((id, _), _)| *id == order_id)
+            .map(|((_, invoice_id), _)| invoice_id)
+            .collect()
+    });
+    ids.iter().filter_map(_get_invoice).collect()
+}
+
+#[ic_cdk::query]
+fn list_accounts(business_id: BusinessId) -> Result<Vec<ChartAccount>, Error> {
+    ensure_business_exists(business_id)?;
+    ensure_role(business_id, Role::Viewer)?;
+    Ok(_list_accounts(business_id))
+}
+
+#[ic_cdk::update]
+fn add_account(business_id: BusinessId, payload: AccountPayload) -> Result<ChartAccount, Error> {
+    ensure_business_exists(business_id)?;
+    ensure_role(business_id, Role::Manager)?;
+    payload.validate()?;
+    let code = payload.code.to_uppercase();
+    if _get_account(business_id, &code).is_some() {
+        return Err(Error::Conflict {
+            msg: format!("the business with id={} already has an account {}", business_id, code),
+        });
+    }
+    if _list_accounts(business_id).len() >= MAX_ACCOUNTS {
+        return Err(Error::LimitExceeded {
+            msg: format!("a business can have at most {} accounts", MAX_ACCOUNTS),
+        });
+    }
+    let account = ChartAccount {
+        business_id,
+        code,
+        name: payload.name,
+        kind: payload.kind,
+        built_in: false,
+    };
+    ensure_fits(&account)?;
+    do_insert_account(&account);
+    audit("add_account", None, Some(&account));
+    Ok(account)
+}
+
+// accounts keep their code; built-in accounts can be renamed but keep their kind as well
+#[ic_cdk::update]
+fn update_account(
+    business_id: BusinessId,
+    code: String,
+    payload: AccountPayload,
+) -> Result<ChartAccount, Error> {
+    match _get_account(business_id, &code) {
+        Some(account) => {
+            ensure_role(business_id, Role::Manager)?;
+            payload.validate()?;
+            if payload.code.to_uppercase() != account.code {
+                return Err(Error::invalid_field("code", "an account's code can't change"));
+            }
+            if account.built_in && payload.kind != account.kind {
+                return Err(Error::invalid_field(
+                    "kind",
+                    "the kind of a built-in account can't change",
+                ));
+            }
+            let updated = ChartAccount {
+                name: payload.name,
+                kind: payload.kind,
+                ..account.clone()
+            };
+            ensure_fits(&updated)?;
+            do_insert_account(&updated);
+            audit("update_account", Some(&account), Some(&updated));
+            Ok(updated)
+        }
+        None => Err(Error::NotFound {
+            msg: format!(
+                "couldn't update an account {} of the business with id={}. account not found",
+                code, business_id
+            ),
+        }),
+    }
+}
+
+#[ic_cdk::query]
+fn get_journal_entry(id: EntryId) -> Result<JournalEntry, Error> {
+    match _get_journal_entry(&id) {
+        Some(entry) => {
+            ensure_role(entry.business_id, Role::Viewer)?;
+            Ok(entry)
+        }
+        None => Err(Error::NotFound {
+            msg: format!("a journal entry with id={} not found", id),
+        }),
+    }
+}
+
+// entries dated within `from..=to`, oldest first
+#[ic_cdk::query]
+fn list_journal_entries(
+    business_id: BusinessId,
+    from: Option<u64>,
+    to: Option<u64>,
+    cursor: Option<EntryId>,
+    limit: u32,
+) -> Result<Page<JournalEntry, EntryId>, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    let page_size = page_size(limit)?;
+    let start = match cursor.and_then(|id| _get_journal_entry(&id)) {
+        Some(entry) => ((business_id, entry.date.max(from.unwrap_or(0))), entry.id),
+        None => ((business_id, from.unwrap_or(0)), EntryId::default()),
+    };
+    let mut entries = _list_journal_entries(start, business_id, to, page_size + 1);
+    let next_cursor = if entries.len() > page_size {
+        entries.pop().map(|entry| entry.id)
+    } else {
+        None
+    };
+    Ok(Page {
+        items: entries,
+        next_cursor,
+    })
+}
+
+#[ic_cdk::update]
+fn post_journal_entry(payload: JournalEntryPayload) -> Result<JournalEntry, Error> {
+    ensure_business_exists(payload.business_id)?;
+    ensure_role(payload.business_id, Role::Manager)?;
+    payload.validate()?;
+    let mut v = Validator::default();
+    let mut lines = Vec::new();
+    for (i, line) in payload.lines.into_iter().enumerate() {
+        let account = line.account.to_uppercase();
+        if _get_account(payload.business_id, &account).is_none() {
+            v.error(
+                &format!("lines[{}].account", i),
+                format!("the business has no account {}", account),
+            );
+        }
+        lines.push(JournalLine { account, ..line });
+    }
+    v.finish()?;
+    let posted_at = time();
+    let entry = do_post_entry(JournalEntry {
+        id: EntryId::default(),
+        business_id: payload.business_id,
+        currency: payload.currency,
+        date: payload.date.unwrap_or(posted_at),
+        memo: payload.memo,
+        source: EntrySource::Manual,
+        lines,
+        posted_by: caller(),
+        posted_at,
+    })?;
+    audit("post_journal_entry", None, Some(&entry));
+    Ok(entry)
+}
+
+#[ic_cdk::query]
+fn get_trial_balance(
+    business_id: BusinessId,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> Result<Vec<TrialBalance>, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    Ok(account_balances(business_id, from, to)?
+        .into_iter()
+        .map(|(currency, accounts)| TrialBalance {
+            currency,
+            total_debit: accounts.iter().map(|account| account.debit).sum(),
+            total_credit: accounts.iter().map(|account| account.credit).sum(),
+            accounts,
+        })
+        .collect())
+}
+
+#[ic_cdk::query]
+fn get_profit_and_loss(
+    business_id: BusinessId,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> Result<Vec<ProfitAndLoss>, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    Ok(account_balances(business_id, from, to)?
+        .into_iter()
+        .map(|(currency, accounts)| {
+            let mut kinds = by_kind(accounts);
+            let revenue = kinds.remove(&AccountKind::Revenue).unwrap_or_default();
+            let expenses = kinds.remove(&AccountKind::Expense).unwrap_or_default();
+            let total_revenue = total_balance(&revenue);
+            let total_expenses = total_balance(&expenses);
+            ProfitAndLoss {
+                currency,
+                revenue,
+                expenses,
+                total_revenue,
+                total_expenses,
+                net_income: total_revenue - total_expenses,
+            }
+        })
+        .collect())
+}
+
+// leave out `from` for the balances as of `to`; with it the sheet shows what changed in between
+#[ic_cdk::query]
+fn get_balance_sheet(
+    business_id: BusinessId,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> Result<Vec<BalanceSheet>, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    Ok(account_balances(business_id, from, to)?
+        .into_iter()
+        .map(|(currency, accounts)| {
+            let mut kinds = by_kind(accounts);
+            let mut take = |kind| kinds.remove(&kind).unwrap_or_default();
+            let assets = take(AccountKind::Asset);
+            let liabilities = take(AccountKind::Liability);
+            let equity = take(AccountKind::Equity);
+            let net_income = total_balance(&take(AccountKind::Revenue))
+                - total_balance(&take(AccountKind::Expense));
+            BalanceSheet {
+                currency,
+                total_assets: total_balance(&assets),
+                total_liabilities: total_balance(&liabilities),
+                total_equity: total_balance(&equity),
+                net_income,
+                assets,
+                liabilities,
+                equity,
+            }
+        })
+        .collect())
+}
+
+// the balance of every account with entries dated within `from..=to`, per currency
+fn account_balances(
+    business_id: BusinessId,
+    from: Option<u64>,
+    to: Option<u64>,
+) -> Result<Vec<(Currency, Vec<AccountBalance>)>, Error> {
+    let start = ((business_id, from.unwrap_or(0)), EntryId::default());
+    let entries = _list_journal_entries(start, business_id, to, MAX_REPORT_ENTRIES + 1);
+    if entries.len() > MAX_REPORT_ENTRIES {
+        return Err(Error::LimitExceeded {
+            msg: format!(
+                "the period has more than {} journal entries; pick a shorter one",
+                MAX_REPORT_ENTRIES
+            ),
+        });
+    }
+    let mut totals: BTreeMap<(String, u8), BTreeMap<String, (u128, u128)>> = BTreeMap::new();
+    for entry in entries {
+        let accounts = totals
+            .entry((entry.currency.code, entry.currency.decimals))
+            .or_default();
+        for line in entry.lines {
+            let (debit, credit) = accounts.entry(line.account).or_default();
+            *debit += line.debit as u128;
+            *credit += line.credit as u128;
+        }
+    }
+    let chart: BTreeMap<String, ChartAccount> = _list_accounts(business_id)
+        .into_iter()
+        .map(|account| (account.code.clone(), account))
+        .collect();
+    Ok(totals
+        .into_iter()
+        .map(|((code, decimals), accounts)| {
+            let balances = accounts
+                .into_iter()
+                .filter_map(|(account, (debit, credit))| {
+                    let account = chart.get(&account)?;
+                    let balance = if account.kind.debit_normal() {
+                        debit as i128 - credit as i128
+                    } else {
+                        credit as i128 - debit as i128
+                    };
+                    Some(AccountBalance {
+                        code: account.code.clone(),
+                        name: account.name.clone(),
+                        kind: account.kind,
+                        debit,
+                        credit,
+                        balance,
+                    })
+                })
+                .collect();
+            (Currency { code, decimals }, balances)
+        })
+        .collect())
+}
+
+fn by_kind(accounts: Vec<AccountBalance>) -> BTreeMap<AccountKind, Vec<AccountBalance>> {
+    let mut kinds: BTreeMap<AccountKind, Vec<AccountBalance>> = BTreeMap::new();
+    for account in accounts {
+        kinds.entry(account.kind).or_default().push(account);
+    }
+    kinds
+}
+
+fn total_balance(accounts: &[AccountBalance]) -> i128 {
+    accounts.iter().map(|account| account.balance).sum()
+}
+
+// books what an order moving to its current status means for the business's accounts: a
+// confirmed order is a sale the buyer owes until it is paid, and cancelling it takes the sale back
+fn order_entries(header: &OrderHeader) -> Result<Vec<JournalEntry>, Error> {
+    let id = header.id;
+    let total = header.total_price.amount;
+    let net = header.subtotal.amount.min(total);
+    let tax = total - net;
+    let sale = [
+        (RECEIVABLES_ACCOUNT, total, 0),
+        (SALES_ACCOUNT, 0, net),
+        (SALES_TAX_ACCOUNT, 0, tax),
+    ];
+    let booked = _has_order_entry(id, &EntrySource::Sale(id));
+    let entries = match header.status {
+        OrderStatus::Confirmed => {
+            let memo = format!("order {} confirmed", id);
+            vec![order_entry(header, EntrySource::Sale(id), memo, &sale)?]
+        }
+        OrderStatus::Paid => {
+            let mut entries = Vec::new();
+            // orders confirmed before there was a journal haven't been booked as sales yet
+            if !booked {
+                let memo = format!("order {} sold", id);
+                entries.push(order_entry(header, EntrySource::Sale(id), memo, &sale)?);
+            }
+            entries.push(order_entry(
+                header,
+                EntrySource::Payment(id),
+                format!("order {} paid", id),
+                &[(CASH_ACCOUNT, total, 0), (RECEIVABLES_ACCOUNT, 0, total)],
+            )?);
+            entries
+        }
+        OrderStatus::Cancelled if booked => vec![order_entry(
+            header,
+            EntrySource::Cancellation(id),
+            format!("order {} cancelled", id),
+            &[
+                (SALES_ACCOUNT, net, 0),
+                (SALES_TAX_ACCOUNT, tax, 0),
+                (RECEIVABLES_ACCOUNT, 0, total),
+            ],
+        )?],
+        _ => Vec::new(),
+    };
+    Ok(entries.into_iter().flatten().collect())
+}
+
+// the tax on the refunded share of the order is given back along with the sale
+fn refund_entry(
+    header: &OrderHeader,
+    amount: u64,
+    return_id: Option<ReturnId>,
+) -> Result<Option<JournalEntry>, Error> {
+    let total = header.total_price.amount;
+    let tax = if total == 0 {
+        0
+    } else {
+        mul_div_round(amount, total - header.subtotal.amount.min(total), total)?
+    };
+    order_entry(
+        header,
+        EntrySource::Refund {
+            order_id: header.id,
+            return_id,
+        },
+        format!("order {} refunded", header.id),
+        &[
+            (SALES_RETURNS_ACCOUNT, amount - tax, 0),
+            (SALES_TAX_ACCOUNT, tax, 0),
+            (CASH_ACCOUNT, 0, amount),
+        ],
+    )
+}
+
+fn overpayment_entry(header: &OrderHeader, amount: u64) -> Result<Option<JournalEntry>, Error> {
+    order_entry(
+        header,
+        EntrySource::Overpayment(header.id),
+        format!("order {} overpaid", header.id),
+        &[(CASH_ACCOUNT, amount, 0), (CUSTOMER_CREDIT_ACCOUNT, 0, amount)],
+    )
+}
+
+// lines without an amount are left out, and there is no entry when none has one
+fn order_entry(
+    header: &OrderHeader,
+    source: EntrySource,
+    memo: String,
+    lines: &[(&str, u64, u64)],
+) -> Result<Option<JournalEntry>, Error> {
+    let lines: Vec<JournalLine> = lines
+        .iter()
+        .filter(|(_, debit, credit)| *debit > 0 || *credit > 0)
+        .map(|(account, debit, credit)| JournalLine {
+            account: account.to_string(),
+            debit: *debit,
+            credit: *credit,
+        })
+        .collect();
+    if lines.is_empty() {
+        return Ok(None);
+    }
+    let posted_at = time();
+    let entry = JournalEntry {
+        id: EntryId::default(),
+        business_id: header.business_id,
+        currency: header.total_price.currency(),
+        date: posted_at,
+        memo,
+        source,
+        lines,
+        posted_by: caller(),
+        posted_at,
+    };
+    ensure_fits(&entry)?;
+    Ok(Some(entry))
+}
+
+fn do_post_entry(mut entry: JournalEntry) -> Result<JournalEntry, Error> {
+    ensure_fits(&entry)?;
+    entry.id = next_id(&JOURNAL_ID_COUNTER)?;
+    do_insert_entry(&entry);
+    Ok(entry)
+}
+
+fn do_insert_entry(entry: &JournalEntry) {
+    JOURNAL_STORAGE.with(|service| service.borrow_mut().insert(entry.id, entry.clone()));
+    JOURNAL_BY_DATE.with(|index| {
+        index
+            .borrow_mut()
+            .insert(((entry.business_id, entry.date), entry.id), ())
+    });
+    if let Some(order_id) = entry.source.order_id() {
+        JOURNAL_BY_ORDER.with(|index| index.borrow_mut().insert((order_id, entry.id), ()));
+    }
+}
+
+fn do_insert_account(account: &ChartAccount) {
+    ACCOUNT_STORAGE.with(|service| {
+        service.borrow_mut().insert(
+            (account.business_id, StorableCode(account.code.clone())),
+            account.clone(),
+        )
+    });
+}
+
+fn built_in_accounts(business_id: BusinessId) -> impl Iterator<Item = ChartAccount> {
+    BUILT_IN_ACCOUNTS.iter().map(move |(code, name, kind)| ChartAccount {
+        business_id,
+        code: code.to_string(),
+        name: name.to_string(),
+        kind: *kind,
+        built_in: true,
+    })
+}
+
+fn _get_account(business_id: BusinessId, code: &str) -> Option<ChartAccount> {
+    let code = code.to_uppercase();
+    ACCOUNT_STORAGE
+        .with(|service| service.borrow().get(&(business_id, StorableCode(code.clone()))))
+        .or_else(|| built_in_accounts(business_id).find(|account| account.code == code))
+}
+
+// the built-in accounts first, then the business's own, each by code
+fn _list_accounts(business_id: BusinessId) -> Vec<ChartAccount> {
+    let mut accounts: BTreeMap<String, ChartAccount> = built_in_accounts(business_id)
+        .map(|account| (account.code.clone(), account))
+        .collect();
+    ACCOUNT_STORAGE.with(|service| {
+        for (_, account) in service
+            .borrow()
+            .range((business_id, StorableCode::default())..)
+            .take_while(|((id, _), _)| *id == business_id)
+        {
+            accounts.insert(account.code.clone(), account);
+        }
+    });
+    let (mut built_in, own): (Vec<_>, Vec<_>) =
+        accounts.into_values().partition(|account| account.built_in);
+    built_in.extend(own);
+    built_in
+}
+
+fn _has_journal_entries(business_id: BusinessId) -> bool {
+    JOURNAL_BY_DATE.with(|index| {
+        index
+            .borrow()
+            .range(((business_id, 0), EntryId::default())..)
+            .next()
+            .is_some_and(|(((id, _), _), _)| id == business_id)
+    })
+}
+
+fn _get_journal_entry(id: &EntryId) -> Option<JournalEntry> {
+    JOURNAL_STORAGE.with(|service| service.borrow().get(id))
+}
+
+// up to `limit` entries of the business from `start` on, dated no later than `to`
+fn _list_journal_entries(
+    start: JournalKey,
+    business_id: BusinessId,
+    to: Option<u64>,
+    limit: usize,
+) -> Vec<JournalEntry> {
+    let to = to.unwrap_or(u64::MAX);
+    let ids: Vec<EntryId> = JOURNAL_BY_DATE.with(|index| {
+        index
+            .borrow()
+            .range(start..)
+            .take_while(|(((id, date), _), _)| *id == business_id && *date <= to)
+            .take(limit)
+            .map(|((_, entry_id), _)| entry_id)
+            .collect()
+    });
+    ids.iter().filter_map(_get_journal_entry).collect()
+}
+
+fn _has_order_entry(order_id: OrderId, source: &EntrySource) -> bool {
+    let ids: Vec<EntryId> = JOURNAL_BY_ORDER.with(|index| {
+        index
+            .borrow()
+            .range((order_id, EntryId::default())..)
+            .take_while(|((id, _), _)| *id == order_id)
+            .map(|((_, entry_id), _)| entry_id)
+            .collect()
+    });
+    ids.iter()
+        .filter_map(_get_journal_entry)
+        .any(|entry| entry.source == *source)
+}
+
+// Sales of every order that isn't cancelled, by the UTC day it was placed on, within
+// `from..=to`. The range is widened to whole days, and periods without sales are left out.
+// The totals are gross sales: returns and refunds don't reduce them, they are booked to the
+// sales returns account instead.
+#[ic_cdk::query]
+fn get_sales_report(
+    business_id: BusinessId,
+    from: Option<u64>,
+    to: Option<u64>,
+    granularity: Granularity,
+) -> Result<SalesReport, Error> {
+    ensure_role(business_id, Role::Viewer)?;
+    let first_day = from.unwrap_or(0) / NANOS_PER_DAY;
+    let last_day = to.unwrap_or(u64::MAX) / NANOS_PER_DAY;
+
+    let days: Vec<((u64, StorableCode), DailySales)> = SALES_BY_DAY.with(|service| {
+        service
+            .borrow()
+            .range(((business_id, first_day), StorableCode::default())..)
+            .take_while(|(((id, day), _), _)| *id == business_id && *day <= last_day)
+            .take(MAX_REPORT_ENTRIES + 1)
+            .map(|(((_, day), currency), sales)| ((day, currency), sales))
+            .collect()
+    });
+    let products: Vec<((ProductId, StorableCode), DailySales)> =
+        PRODUCT_SALES_BY_DAY.with(|service| {
+            service
+                .borrow()
+                .range(((business_id, first_day), Default::default())..)
+                .take_while(|(((id, day), _), _)| *id == business_id && *day <= last_day)
+                .take(MAX_REPORT_ENTRIES + 1)
+                .map(|((_, product), sales)| (product, sales))
+                .collect()
+        });
+    if days.len() > MAX_REPORT_ENTRIES {
+        return Err(Error::LimitExceeded {
+            msg: format!(
+                "the range has more than {} days of sales; pick a shorter one",
+                MAX_REPORT_ENTRIES
+            ),
+        });
+    }
+    if products.len() > MAX_REPORT_ENTRIES {
+        return Err(Error::LimitExceeded {
+            msg: format!(
+                "the range has more than {} daily sales of single products; pick a shorter one",
+                MAX_REPORT_ENTRIES
+            ),
+        });
+    }
+
+    let mut periods: BTreeMap<(u64, StorableCode), DailySales> = BTreeMap::new();
+    for ((day, currency), sales) in days {
+        let key = (period_start(day, granularity), currency);
+        match periods.get_mut(&key) {
+            Some(period) => add_sales(period, &sales)?,
+            None => {
+                periods.insert(key, sales);
+            }
+        }
+    }
+    let mut top: BTreeMap<(ProductId, StorableCode), DailySales> = BTreeMap::new();
+    for (key, sales) in products {
+        match top.get_mut(&key) {
+            Some(product) => add_sales(product, &sales)?,
+            None => {
+                top.insert(key, sales);
+            }
+        }
+    }
+    let mut top_products: Vec<ProductSales> = top
+        .into_iter()
+        .map(|((product_id, _), sales)| ProductSales {
+            product_id,
+            units: sales.units,
+            revenue: sales.revenue,
+        })
+        .collect();
+    top_products.sort_by_key(|product| std::cmp::Reverse(product.units));
+    top_products.truncate(TOP_PRODUCTS);
+
+    Ok(SalesReport {
+        periods: periods
+            .into_iter()
+            .map(|((start, _), sales)| SalesPeriod {
+                start: start.saturating_mul(NANOS_PER_DAY),
+                order_count: sales.order_count,
+                units: sales.units,
+                average_order_value: Money {
+                    amount: sales.revenue.amount / sales.order_count.max(1),
+                    ..sales.revenue.clone()
+                },
+                revenue: sales.revenue,
+            })
+            .collect(),
+        top_products,
+    })
+}
+
+fn add_sales(totals: &mut DailySales, sales: &DailySales) -> Result<(), Error> {
+    totals.order_count = totals.order_count.saturating_add(sales.order_count);
+    totals.units = totals.units.saturating_add(sales.units);
+    totals.revenue = totals.revenue.checked_add(&sales.revenue)?;
+    Ok(())
+}
+
+// the first day of the period `day` falls in, both counted in days since 1970-01-01
+fn period_start(day: u64, granularity: Granularity) -> u64 {
+    match granularity {
+        Granularity::Day => day,
+        // 1970-01-01 was a Thursday, so its week is cut short at the epoch
+        Granularity::Week => day.saturating_sub((day + 3) % 7),
+        Granularity::Month => day + 1 - day_of_month(day),
+    }
+}
+
+// the day of the month, from 1, of a day counted since 1970-01-01, after Howard Hinnant's
+// `civil_from_days`
+fn day_of_month(day: u64) -> u64 {
+    let z = day + 719_468;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    day_of_year - (153 * month_index + 2) / 5 + 1
+}
+
+// adds an order to the sales of the day it was placed on, or takes it back out. Each order is
+// counted at most once, so counting one again is harmless.
+fn do_count_sales(order: &Order, counted: bool) {
+    if SALES_COUNTED.with(|service| service.borrow().contains_key(&order.id)) == counted {
+        return;
+    }
+    let day = (order.business_id, order.created_at / NANOS_PER_DAY);
+    let currency = StorableCode(order.total_price.currency.clone());
+    let units = order
+        .lines
+        .iter()
+        .fold(0u64, |units, line| units.saturating_add(line.quantity as u64));
+    SALES_BY_DAY.with(|service| {
+        let mut service = service.borrow_mut();
+        let key = (day, currency.clone());
+        let sales = service.get(&key);
+        match adjust_sales(sales, units, &order.total_price, counted) {
+            Some(sales) => service.insert(key, sales),
+            None => service.remove(&key),
+        }
+    });
+    PRODUCT_SALES_BY_DAY.with(|service| {
+        let mut service = service.borrow_mut();
+        for line in order.lines.iter() {
+            let key = (day, (line.product_id, currency.clone()));
+            let sales = service.get(&key);
+            let units = line.quantity as u64;
+            match adjust_sales(sales, units, &line.line_total, counted) {
+                Some(sales) => service.insert(key, sales),
+                None => service.remove(&key),
+            };
+        }
+    });
+    SALES_COUNTED.with(|service| {
+        let mut service = service.borrow_mut();
+        if counted {
+            service.insert(order.id, ());
+        } else {
+            service.remove(&order.id);
+        }
+    });
+}
+
+// adds or takes away one order; none once nothing is left, so days without sales don't
+// linger in the maps
+fn adjust_sales(
+    sales: Option<DailySales>,
+    units: u64,
+    revenue: &Money,
+    add: bool,
+) -> Option<DailySales> {
+    let mut sales = sales.unwrap_or_else(|| DailySales {
+        order_count: 0,
+        units: 0,
+        revenue: Money {
+            amount: 0,
+            ..revenue.clone()
+        },
+    });
+    if add {
+        sales.order_count = sales.order_count.saturating_add(1);
+        sales.units = sales.units.saturating_add(units);
+        sales.revenue.amount = sales.revenue.amount.saturating_add(revenue.amount);
+    } else {
+        sales.order_count = sales.order_count.saturating_sub(1);
+        sales.units = sales.units.saturating_sub(units);
+        sales.revenue.amount = sales.revenue.amount.saturating_sub(revenue.amount);
+    }
+    (sales.order_count > 0).then_some(sales)
+}
+
+// counts up to `limit` orders that were placed before there were sales totals
+fn count_order_sales(start: u64, limit: usize) -> (usize, Option<u64>) {
+    let mut ids: Vec<OrderId> = ORDER_STORAGE.with(|service| {
+        service
+            .borrow()
+            .range(OrderId::new(start)..)
+            .take(limit + 1)
+            .map(|(id, _)| id)
+            .collect()
+    });
+    let next_key = if ids.len() > limit {
+        ids.pop().map(|id| id.raw())
+    } else {
+        None
     };
-    do_insert_product(&product);
-    Some(product)
+    for order in ids.iter().filter_map(_get_order) {
+        if order.status != OrderStatus::Cancelled {
+            do_count_sales(&order, true);
+        }
+    }
+    (ids.len(), next_key)
+}
+
+// where a buyer who can't approve transfers sends the total of an order, plus one ledger fee.
+// Buyers aren't members of the business, so this is open to anyone; the account only depends
+// on the canister and the order id, so it gives nothing away that couldn't be worked out.
+#[ic_cdk::query]
+fn get_order_deposit_account(id: OrderId) -> Result<Account, Error> {
+    match _get_order_header(id) {
+        Some(_) => Ok(order_deposit_account(id)),
+        None => Err(Error::NotFound {
+            msg: format!("an order with id={} not found", id),
+        }),
+    }
 }
 
+// Checks the order's deposit account on the ledger of its currency. Once it holds the total plus
+// one ledger fee, the funds are swept into the business's account and the order moves to Paid;
+// anything above that is added to the payment's `overpaid` so it can be refunded. Funds that
+// arrive after the order was paid are swept and counted as overpaid the same way. Until the
+// total is covered the order is returned unchanged.
 #[ic_cdk::update]
-fn update_product(id: u64, payload: ProductPayload) -> Result<Product, Error> {
-    match PRODUCT_STORAGE.with(|service| service.borrow().get(&id)) {
-        Some(mut product) => {
-            product.name = payload.name;
-            product.description = payload.description;
-            product.price = payload.price;
-            product.updated_at = Some(time());
-            do_insert_product(&product);
-            Ok(product)
+async fn check_order_payment(id: OrderId) -> Result<Order, Error> {
+    let before = match _get_order(&id) {
+        Some(order) => order,
+        None => {
+            return Err(Error::NotFound {
+                msg: format!("couldn't check an order with id={}. order not found", id),
+            })
         }
-        None => Err(Error::NotFound {
-            msg: format!(
-                "couldn't update a product with id={}. product not found",
-                id
-            ),
+    };
+    let header = before.header();
+    if before.payment.is_none() {
+        ensure_can_transition(&header, OrderStatus::Paid)?;
+    }
+    let ledger = ensure_ledger(&header.total_price)?;
+    let _guard = PaymentGuard::acquire(id)?;
+
+    let deposit = order_deposit_account(id);
+    let balance = nat_to_u64(&icrc1_balance_of(ledger.ledger, deposit.clone()).await?)?;
+    let fee = nat_to_u64(&icrc1_fee(ledger.ledger).await?)?;
+    let due = match &before.payment {
+        Some(_) => 0,
+        None => header.total_price.amount,
+    };
+    if balance <= fee || balance - fee < due {
+        return Ok(before);
+    }
+
+    // everything the sweep writes is built and checked before the funds move, so once they have
+    // moved nothing is left that can fail
+    let to = business_account(header.business_id);
+    let extra = Money {
+        amount: balance - fee - due,
+        ..header.total_price.clone()
+    };
+    let mut payment = match before.payment.clone() {
+        Some(mut payment) => {
+            payment.overpaid = payment.overpaid.checked_add(&extra)?;
+            payment
+        }
+        None => Payment {
+            method: PaymentMethod::Deposit,
+            ledger: ledger.ledger,
+            from: deposit.clone(),
+            to: to.clone(),
+            amount: header.total_price.clone(),
+            overpaid: extra.clone(),
+            // the real index is known once the ledger answers; blocks are numbered with u64s
+            block_index: Nat::from(u64::MAX),
+            paid_at: time(),
+        },
+    };
+    ensure_fits(&payment)?;
+    let paid = match before.payment {
+        Some(_) => None,
+        None => Some(prepare_transition(header.clone(), OrderStatus::Paid)?),
+    };
+    let mut overpayment = overpayment_entry(&header, extra.amount)?;
+    if let Some(entry) = overpayment.as_mut() {
+        entry.id = next_id(&JOURNAL_ID_COUNTER)?;
+    }
+
+    let block_index = icrc1_transfer(
+        ledger.ledger,
+        TransferArg {
+            from_subaccount: deposit.subaccount.clone(),
+            to,
+            amount: Nat::from(balance - fee),
+            fee: Some(Nat::from(fee)),
+            memo: Some(id.raw().to_be_bytes().to_vec()),
+            created_at_time: Some(time()),
+        },
+    )
+    .await?;
+
+    // the guard kept the order from changing while the ledger was called
+    if paid.is_some() {
+        payment.block_index = block_index;
+        payment.paid_at = time();
+    }
+    ORDER_PAYMENTS.with(|service| service.borrow_mut().insert(id, payment.clone()));
+    let order = match paid {
+        Some(paid) => apply_transition("check_order_payment", paid),
+        None => Order {
+            payment: Some(payment),
+            ..before.clone()
+        },
+    };
+    if let Some(entry) = overpayment {
+        do_insert_entry(&entry);
+        audit("check_order_payment", None, Some(&entry));
+    }
+    audit("check_order_payment", Some(&before), Some(&order));
+    Ok(order)
+}
+
+async fn icrc1_balance_of(ledger: Principal, account: Account) -> Result<Nat, Error> {
+    let result: Result<(Nat,), _> = ic_cdk::call(ledger, "icrc1_balance_of", (account,)).await;
+    result.map(|(balance,)| balance).map_err(|(code, msg)| Error::PaymentFailed {
+        msg: format!("couldn't call the ledger {}: {:?} {}", ledger, code, msg),
+    })
+}
+
+async fn icrc1_fee(ledger: Principal) -> Result<Nat, Error> {
+    let result: Result<(Nat,), _> = ic_cdk::call(ledger, "icrc1_fee", ()).await;
+    result.map(|(fee,)| fee).map_err(|(code, msg)| Error::PaymentFailed {
+        msg: format!("couldn't call the ledger {}: {:?} {}", ledger, code, msg),
+    })
+}
+
+async fn icrc1_transfer(ledger: Principal, args: TransferArg) -> Result<Nat, Error> {
+    let result: Result<(Result<Nat, TransferError>,), _> =
+        ic_cdk::call(ledger, "icrc1_transfer", (args,)).await;
+    match result {
+        Ok((Ok(block_index),)) => Ok(block_index),
+        Ok((Err(e),)) => Err(Error::PaymentFailed {
+            msg: format!("the ledger {} rejected the transfer: {:?}", ledger, e),
+        }),
+        Err((code, msg)) => Err(Error::PaymentFailed {
+            msg: format!("couldn't call the ledger {}: {:?} {}", ledger, code, msg),
         }),
     }
 }
 
-fn do_insert_product(product: &Product) {
-    PRODUCT_STORAGE
-        .with(|service| service.borrow_mut().insert(product.id, product.clone()));
+async fn icrc2_transfer_from(ledger: Principal, args: TransferFromArgs) -> Result<Nat, Error> {
+    let result: Result<(Result<Nat, TransferFromError>,), _> =
+        ic_cdk::call(ledger, "icrc2_transfer_from", (args,)).await;
+    match result {
+        Ok((Ok(block_index),)) => Ok(block_index),
+        Ok((Err(e),)) => Err(Error::PaymentFailed {
+            msg: format!("the ledger {} rejected the transfer: {:?}", ledger, e),
+        }),
+        Err((code, msg)) => Err(Error::PaymentFailed {
+            msg: format!("couldn't call the ledger {}: {:?} {}", ledger, code, msg),
+        }),
+    }
 }
 
-#[ic_cdk::update]
-fn delete_product(id: u64) -> Result<Product, Error> {
-    match PRODUCT_STORAGE.with(|service| service.borrow_mut().remove(&id)) {
-        Some(product) => Ok(product),
-        None => Err(Error::NotFound {
+// the ledger of `amount`'s currency, which has to use the same number of decimals
+fn ensure_ledger(amount: &Money) -> Result<LedgerConfig, Error> {
+    match LEDGERS.with(|service| service.borrow().get(&StorableCode(amount.currency.clone()))) {
+        Some(config) if config.currency == amount.currency() => Ok(config),
+        Some(config) => Err(Error::CurrencyMismatch {
             msg: format!(
-                "couldn't delete a product with id={}. product not found.",
-                id
+                "the {} ledger uses {} decimals but the amount has {}",
+                config.currency.code, config.currency.decimals, amount.decimals
             ),
         }),
+        None => Err(Error::NotFound {
+            msg: format!("no ledger is set for {}", amount.currency),
+        }),
     }
 }
 
-#[ic_cdk::query]
-fn get_order(id: u64) -> Result<Order, Error> {
-    match _get_order(&id) {
-        Some(order) => Ok(order),
+fn nat_to_u64(value: &Nat) -> Result<u64, Error> {
+    u64::try_from(&value.0).map_err(|_| Error::LimitExceeded {
+        msg: format!("the ledger amount {} doesn't fit in 64 bits", value),
+    })
+}
+
+fn order_deposit_account(id: OrderId) -> Account {
+    Account {
+        owner: ic_cdk::id(),
+        subaccount: Some(subaccount(ORDER_SUBACCOUNT_TAG, id.raw())),
+    }
+}
+
+fn business_account(business_id: BusinessId) -> Account {
+    Account {
+        owner: ic_cdk::id(),
+        subaccount: Some(subaccount(BUSINESS_SUBACCOUNT_TAG, business_id.raw())),
+    }
+}
+
+// a tag byte saying what the subaccount belongs to, then the id in big-endian bytes, zero padded
+fn subaccount(tag: u8, id: u64) -> Vec<u8> {
+    let mut subaccount = vec![0; 32];
+    subaccount[0] = tag;
+    subaccount[1..9].copy_from_slice(&id.to_be_bytes());
+    subaccount
+}
+
+fn _get_order_payment(id: &OrderId) -> Option<Payment> {
+    ORDER_PAYMENTS.with(|service| service.borrow().get(id))
+}
+
+// exchange rates are maintained by the canister's controllers
+#[ic_cdk::update]
+fn set_exchange_rate(base: Currency, quote: Currency, rate: u64) -> Result<ExchangeRate, Error> {
+    ensure_controller()?;
+    let mut v = Validator::default();
+    v.currency("base", &base);
+    v.currency("quote", &quote);
+    if base.code == quote.code {
+        v.error("quote", "must differ from the base currency".to_string());
+    }
+    v.range("rate", rate, 1, u64::MAX);
+    v.finish()?;
+    let exchange_rate = ExchangeRate {
+        base,
+        quote,
+        rate,
+        updated_by: caller(),
+        updated_at: time(),
+    };
+    let before = EXCHANGE_RATES.with(|service| {
+        service.borrow_mut().insert(
+            RateKey {
+                base: exchange_rate.base.code.clone(),
+                quote: exchange_rate.quote.code.clone(),
+            },
+            exchange_rate.clone(),
+        )
+    });
+    audit("set_exchange_rate", before.as_ref(), Some(&exchange_rate));
+    Ok(exchange_rate)
+}
+
+#[ic_cdk::update]
+fn remove_exchange_rate(base: String, quote: String) -> Result<ExchangeRate, Error> {
+    ensure_controller()?;
+    match EXCHANGE_RATES.with(|service| {
+        service.borrow_mut().remove(&RateKey {
+            base: base.clone(),
+            quote: quote.clone(),
+        })
+    }) {
+        Some(exchange_rate) => {
+            audit("remove_exchange_rate", Some(&exchange_rate), None);
+            Ok(exchange_rate)
+        }
         None => Err(Error::NotFound {
-            msg: format!("an order with id={} not found", id),
+            msg: format!("a {}/{} exchange rate not found", base, quote),
         }),
     }
 }
 
-#[ic_cdk::update]
-fn create_order(order_payload: OrderPayload) -> Result<Order, Error> {
-    let order_id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
+#[ic_cdk::query]
+fn list_exchange_rates() -> Result<Vec<ExchangeRate>, Error> {
+    Ok(EXCHANGE_RATES.with(|service| service.borrow().iter().map(|(_, rate)| rate).collect()))
+}
+
+fn _get_exchange_rate(base: &str, quote: &str) -> Option<ExchangeRate> {
+    EXCHANGE_RATES.with(|service| {
+        service.borrow().get(&RateKey {
+            base: base.to_string(),
+            quote: quote.to_string(),
         })
-        .expect("cannot increment id counter");
+    })
+}
+
+#[ic_cdk::query]
+fn search_businesses(query: String, limit: u32) -> Result<Vec<Business>, Error> {
+    Ok(search(SearchKind::Business, &query, limit)?
+        .iter()
+        .map(|id| BusinessId::new(*id))
+        .filter_map(|id| _get_business(&id))
+        .collect())
+}
 
-    let mut order_products = Vec::new();
-    let mut total_price = 0;
+#[ic_cdk::query]
+fn search_products(query: String, limit: u32) -> Result<Vec<Product>, Error> {
+    Ok(search(SearchKind::Product, &query, limit)?
+        .iter()
+        .map(|id| ProductId::new(*id))
+        .filter_map(|id| _get_product(&id))
+        .collect())
+}
 
-    for product_id in order_payload.product_ids.iter() {
-        match _get_product(product_id) {
-            Some(product) => {
-                order_products.push(product.clone());
-                total_price += product.price;
+// ranks entities by the summed weight of the query tokens they contain, best match first;
+// ties go to the older entity
+fn search(kind: SearchKind, query: &str, limit: u32) -> Result<Vec<u64>, Error> {
+    let page_size = page_size(limit)?;
+    let mut scores: BTreeMap<u64, u32> = BTreeMap::new();
+    for token in tokenize(query).into_keys().take(MAX_QUERY_TOKENS) {
+        let start = SearchKey {
+            kind,
+            token: token.clone(),
+            entity_id: 0,
+        };
+        SEARCH_INDEX.with(|index| {
+            for (key, weight) in index
+                .borrow()
+                .range(start..)
+                .take_while(|(key, _)| key.kind == kind && key.token == token)
+                .take(MAX_POSTINGS_PER_TOKEN)
+            {
+                *scores.entry(key.entity_id).or_default() += weight;
             }
-            None => {
-                return Err(Error::NotFound {
-                    msg: format!("product with id={} not found", product_id),
-                });
+        });
+    }
+
+    let mut ranked: Vec<(u64, u32)> = scores.into_iter().collect();
+    ranked.sort_by(|(a_id, a_score), (b_id, b_score)| b_score.cmp(a_score).then(a_id.cmp(b_id)));
+    Ok(ranked
+        .into_iter()
+        .take(page_size)
+        .map(|(id, _)| id)
+        .collect())
+}
+
+// lowercases the text and splits it on anything that isn't a letter or digit, counting how
+// often each token occurs; single characters are dropped as noise
+fn tokenize(text: &str) -> BTreeMap<String, u32> {
+    let mut tokens = BTreeMap::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+    {
+        let mut token = word.to_lowercase();
+        if token.len() > MAX_TOKEN_LEN {
+            let mut end = MAX_TOKEN_LEN;
+            while !token.is_char_boundary(end) {
+                end -= 1;
             }
+            token.truncate(end);
         }
+        *tokens.entry(token).or_insert(0) += 1;
     }
+    tokens
+}
 
-    let order = Order {
-        id: order_id,
-        products: order_products,
-        total_price,
-        created_at: time(),
-        updated_at: None,
-    };
+fn weighted_tokens(name: &str, rest: &[&str]) -> BTreeMap<String, u32> {
+    let mut tokens = BTreeMap::new();
+    for (token, count) in tokenize(name) {
+        *tokens.entry(token).or_insert(0) += count * NAME_WEIGHT;
+    }
+    for text in rest {
+        for (token, count) in tokenize(text) {
+            *tokens.entry(token).or_insert(0) += count;
+        }
+    }
+    tokens
+}
 
-    do_insert_order(&order);
-    Ok(order)
+fn index_business(business: &Business) {
+    let tokens = weighted_tokens(&business.name, &[&business.description, &business.address]);
+    do_index(SearchKind::Business, business.id.raw(), tokens);
 }
 
-#[ic_cdk::update]
-fn update_order(id: u64, order_payload: OrderPayload) -> Result<Order, Error> {
-    match ORDER_STORAGE.with(|service| service.borrow().get(&id)) {
-        Some(mut order) => {
-            let mut new_products = Vec::new();
-            let mut new_total_price = 0;
+fn unindex_business(business: &Business) {
+    let tokens = weighted_tokens(&business.name, &[&business.description, &business.address]);
+    do_unindex(SearchKind::Business, business.id.raw(), tokens);
+}
 
-            for product_id in order_payload.product_ids.iter() {
-                match _get_product(product_id) {
-                    Some(product) => {
-                        new_products.push(product.clone());
-                        new_total_price += product.price;
-                    }
-                    None => {
-                        return Err(Error::NotFound {
-                            msg: format!("product with id={} not found", product_id),
-                        });
-                    }
-                }
-            }
+fn index_product(product: &Product) {
+    let tokens = weighted_tokens(&product.name, &[&product.description]);
+    do_index(SearchKind::Product, product.id.raw(), tokens);
+}
 
-            order.products = new_products;
-            order.total_price = new_total_price;
-            order.updated_at = Some(time());
-            do_insert_order(&order);
-            Ok(order)
+fn unindex_product(product: &Product) {
+    let tokens = weighted_tokens(&product.name, &[&product.description]);
+    do_unindex(SearchKind::Product, product.id.raw(), tokens);
+}
+
+fn do_index(kind: SearchKind, entity_id: u64, tokens: BTreeMap<String, u32>) {
+    SEARCH_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for (token, weight) in tokens {
+            index.insert(
+                SearchKey {
+                    kind,
+                    token,
+                    entity_id,
+                },
+                weight,
+            );
         }
-        None => Err(Error::NotFound {
-            msg: format!("couldn't update an order with id={}. order not found", id),
-        }),
-    }
+    });
 }
 
-#[ic_cdk::update]
-fn delete_order(id: u64) -> Result<Order, Error> {
-    match ORDER_STORAGE.with(|service| service.borrow_mut().remove(&id)) {
-        Some(order) => Ok(order),
-        None => Err(Error::NotFound {
-            msg: format!("couldn't delete an order with id={}. order not found", id),
-        }),
+fn do_unindex(kind: SearchKind, entity_id: u64, tokens: BTreeMap<String, u32>) {
+    SEARCH_INDEX.with(|index| {
+        let mut index = index.borrow_mut();
+        for token in tokens.into_keys() {
+            index.remove(&SearchKey {
+                kind,
+                token,
+                entity_id,
+            });
+        }
+    });
+}
+
+fn page_size(limit: u32) -> Result<usize, Error> {
+    if limit == 0 {
+        return Err(Error::invalid_field("limit", "must be at least 1"));
     }
+    if limit > MAX_PAGE_SIZE {
+        return Err(Error::LimitExceeded {
+            msg: format!("limit={} is above the maximum of {}", limit, MAX_PAGE_SIZE),
+        });
+    }
+    Ok(limit as usize)
 }
 
-fn do_insert_order(order: &Order) {
-    ORDER_STORAGE
-        .with(|service| service.borrow_mut().insert(order.id, order.clone()));
+// reads at most one page of records starting at `cursor` (inclusive) without touching the rest of the map
+fn paginate<K: EntityId, T: BoundedStorable>(
+    map: &StableBTreeMap<K, T, Memory>,
+    cursor: Option<K>,
+    limit: u32,
+) -> Result<Page<T, K>, Error> {
+    let page_size = page_size(limit)?;
+    let mut items: Vec<(K, T)> = map
+        .range(cursor.unwrap_or(K::new(0))..)
+        .take(page_size + 1)
+        .collect();
+    let next_cursor = if items.len() > page_size {
+        items.pop().map(|(id, _)| id)
+    } else {
+        None
+    };
+    Ok(Page {
+        items: items.into_iter().map(|(_, item)| item).collect(),
+        next_cursor,
+    })
+}
+
+fn next_id<K: EntityId>(counter: &'static LocalKey<RefCell<IdCell>>) -> Result<K, Error> {
+    counter.with(|counter| {
+        let current_value = *counter.borrow().get();
+        let next_value = current_value.checked_add(1).ok_or_else(|| Error::LimitExceeded {
+            msg: "the id space is exhausted".to_string(),
+        })?;
+        counter
+            .borrow_mut()
+            .set(next_value)
+            .map(|_| K::new(next_value))
+            .map_err(|e| Error::Internal {
+                msg: format!("cannot increment id counter: {:?}", e),
+            })
+    })
 }
 
-fn _get_business(id: &u64) -> Option<Business> {
+fn _get_business(id: &BusinessId) -> Option<Business> {
     BUSINESS_STORAGE.with(|service| service.borrow().get(id))
 }
 
-fn _get_product(id: &u64) -> Option<Product> {
+fn _get_product(id: &ProductId) -> Option<Product> {
     PRODUCT_STORAGE.with(|service| service.borrow().get(id))
 }
 
-fn _get_order(id: &u64) -> Option<Order> {
-    ORDER_STORAGE.with(|service| service.borrow().get(id))
+fn _get_order(id: &OrderId) -> Option<Order> {
+    match ORDER_STORAGE.with(|service| service.borrow().get(id)) {
+        Some(header) => {
+            let lines = _get_order_lines(&header);
+            let tax_lines = _get_order_tax_lines(&header);
+            Some(Order::from_parts(header, lines, tax_lines, _get_order_payment(id)))
+        }
+        None => LEGACY_ORDER_STORAGE
+            .with(|service| service.borrow().get(&id.raw()))
+            .map(Order::from),
+    }
+}
+
+fn _get_order_header(id: OrderId) -> Option<OrderHeader> {
+    ORDER_STORAGE
+        .with(|service| service.borrow().get(&id))
+        .or_else(|| _get_order(&id).map(|order| order.header()))
+}
+
+fn _get_order_lines(header: &OrderHeader) -> Vec<OrderLine> {
+    ORDER_LINE_STORAGE.with(|service| {
+        service
+            .borrow()
+            .range((header.id, 0)..(header.id, header.line_count))
+            .map(|(_, line)| line)
+            .collect()
+    })
+}
+
+fn _get_order_tax_lines(header: &OrderHeader) -> Vec<TaxLine> {
+    ORDER_TAX_LINE_STORAGE.with(|service| {
+        service
+            .borrow()
+            .range((header.id, 0)..(header.id, header.tax_line_count))
+            .map(|(_, line)| line)
+            .collect()
+    })
 }
 
 ic_cdk::export_candid!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a record as an older release stored it: bare Candid for layout version 0, otherwise in an
+    // envelope
+    fn stored<T: CandidType>(version: u8, record: &T) -> Vec<u8> {
+        let payload = Encode!(record).unwrap();
+        if version == 0 {
+            payload
+        } else {
+            [vec![ENVELOPE_TAG, version], payload].concat()
+        }
+    }
+
+    fn usd(amount: u64) -> Money {
+        Money::legacy(amount)
+    }
+
+    fn history() -> Vec<StatusChange> {
+        vec![StatusChange {
+            status: OrderStatus::Confirmed,
+            changed_by: Principal::anonymous(),
+            changed_at: 7,
+        }]
+    }
+
+    fn money(amount: u64, currency: &str, decimals: u8) -> Money {
+        Money {
+            amount,
+            currency: currency.to_string(),
+            decimals,
+        }
+    }
+
+    #[test]
+    fn mul_div_round_rounds_halves_up() {
+        assert_eq!(mul_div_round(10, 3, 5).unwrap(), 6);
+        // 2.5 and 3.5 both go up, where rounding half to even would give 2 and 4
+        assert_eq!(mul_div_round(5, 1, 2).unwrap(), 3);
+        assert_eq!(mul_div_round(7, 1, 2).unwrap(), 4);
+        assert_eq!(mul_div_round(149, 1, 100).unwrap(), 1);
+        assert_eq!(mul_div_round(150, 1, 100).unwrap(), 2);
+        assert_eq!(mul_div_round(0, 7, 3).unwrap(), 0);
+    }
+
+    #[test]
+    fn mul_div_round_works_past_u64_in_between_and_reports_overflow() {
+        assert_eq!(mul_div_round(u64::MAX, 3, 3).unwrap(), u64::MAX);
+        assert_eq!(mul_div_round(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert!(matches!(mul_div_round(u64::MAX, 2, 1), Err(Error::LimitExceeded { .. })));
+        assert!(matches!(mul_div_round(u64::MAX, 3, 2), Err(Error::LimitExceeded { .. })));
+    }
+
+    #[test]
+    fn money_only_adds_up_in_one_currency() {
+        let sum = usd(150).checked_add(&usd(275)).unwrap();
+        assert_eq!(sum, usd(425));
+        assert!(matches!(
+            usd(1).checked_add(&money(1, "EUR", 2)),
+            Err(Error::CurrencyMismatch { .. })
+        ));
+        assert!(matches!(
+            usd(1).checked_add(&money(1, "USD", 3)),
+            Err(Error::CurrencyMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn money_reports_overflow() {
+        assert!(matches!(usd(u64::MAX).checked_add(&usd(1)), Err(Error::LimitExceeded { .. })));
+        assert_eq!(usd(u64::MAX).checked_add(&usd(0)).unwrap(), usd(u64::MAX));
+        assert_eq!(usd(250).checked_mul(4).unwrap(), usd(1000));
+        assert!(matches!(usd(u64::MAX / 2 + 1).checked_mul(2), Err(Error::LimitExceeded { .. })));
+    }
+
+    #[test]
+    fn money_displays_in_major_units() {
+        assert_eq!(usd(1205).to_string(), "12.05 USD");
+        assert_eq!(usd(5).to_string(), "0.05 USD");
+        assert_eq!(money(42, "JPY", 0).to_string(), "42 JPY");
+        assert_eq!(money(1, "ICP", 8).to_string(), "0.00000001 ICP");
+    }
+
+    // `rate` is how many units of `quote` one unit of `base` is worth, with 8 decimals
+    fn exchange_rate(base: (&str, u8), quote: (&str, u8), rate: u64) -> ExchangeRate {
+        ExchangeRate {
+            base: Currency {
+                code: base.0.to_string(),
+                decimals: base.1,
+            },
+            quote: Currency {
+                code: quote.0.to_string(),
+                decimals: quote.1,
+            },
+            rate,
+            updated_by: Principal::anonymous(),
+            updated_at: 0,
+        }
+    }
+
+    #[test]
+    fn converts_between_currencies_rounding_half_up() {
+        let usd_eur = exchange_rate(("USD", 2), ("EUR", 2), 92_000_000);
+        assert_eq!(usd_eur.convert(&usd(1000)).unwrap(), money(920, "EUR", 2));
+        assert_eq!(usd_eur.convert(&usd(1)).unwrap(), money(1, "EUR", 2));
+        let halves = exchange_rate(("USD", 2), ("EUR", 2), 50_000_000);
+        assert_eq!(halves.convert(&usd(1)).unwrap().amount, 1);
+        assert_eq!(halves.convert(&usd(3)).unwrap().amount, 2);
+        assert_eq!(halves.convert(&usd(4)).unwrap().amount, 2);
+    }
+
+    #[test]
+    fn converts_between_different_decimals() {
+        let usd_jpy = exchange_rate(("USD", 2), ("JPY", 0), 15_000_000_000);
+        assert_eq!(usd_jpy.convert(&usd(1)).unwrap(), money(2, "JPY", 0));
+        assert_eq!(usd_jpy.convert(&usd(199)).unwrap(), money(299, "JPY", 0));
+        let icp_usd = exchange_rate(("ICP", 8), ("USD", 2), 1_234_000_000);
+        assert_eq!(icp_usd.convert(&money(100_000_000, "ICP", 8)).unwrap(), usd(1234));
+        assert_eq!(icp_usd.convert(&money(400_000, "ICP", 8)).unwrap(), usd(5));
+        assert_eq!(icp_usd.convert(&money(399_999, "ICP", 8)).unwrap(), usd(5));
+        assert_eq!(icp_usd.convert(&money(364_000, "ICP", 8)).unwrap(), usd(4));
+    }
+
+    #[test]
+    fn refuses_other_currencies_and_overflow() {
+        let usd_jpy = exchange_rate(("USD", 2), ("JPY", 0), 15_000_000_000);
+        assert!(matches!(
+            usd_jpy.convert(&money(100, "EUR", 2)),
+            Err(Error::CurrencyMismatch { .. })
+        ));
+        assert!(matches!(
+            usd_jpy.convert(&money(100, "USD", 3)),
+            Err(Error::CurrencyMismatch { .. })
+        ));
+        assert!(matches!(usd_jpy.convert(&usd(u64::MAX)), Err(Error::LimitExceeded { .. })));
+    }
+
+    fn tax_rate(category: &str, rate: u32, inclusive: bool) -> TaxRate {
+        TaxRate {
+            name: "VAT".to_string(),
+            jurisdiction: "DE".to_string(),
+            category: category.to_string(),
+            rate,
+            inclusive,
+        }
+    }
+
+    fn taxable(categories: &[(&str, u64)]) -> BTreeMap<String, Money> {
+        categories
+            .iter()
+            .map(|(category, amount)| (category.to_string(), usd(*amount)))
+            .collect()
+    }
+
+    // (category, taxable amount, tax) of every line
+    fn taxes(rates: &[TaxRate], categories: &[(&str, u64)]) -> Vec<(String, u64, u64)> {
+        compute_tax_lines(rates, &taxable(categories))
+            .unwrap()
+            .into_iter()
+            .map(|line| (line.rate.category, line.taxable_amount.amount, line.tax.amount))
+            .collect()
+    }
+
+    #[test]
+    fn exclusive_taxes_come_on_top_of_the_line_totals() {
+        let rates = [tax_rate("food", 100_000, false)];
+        assert_eq!(taxes(&rates, &[("food", 1000)]), vec![("food".to_string(), 1000, 100)]);
+        // 0.5 rounds up and 0.45 down
+        let rates = [tax_rate("food", 50_000, false)];
+        assert_eq!(taxes(&rates, &[("food", 10)])[0].2, 1);
+        assert_eq!(taxes(&rates, &[("food", 9)])[0].2, 0);
+    }
+
+    #[test]
+    fn inclusive_taxes_are_taken_out_of_the_line_totals() {
+        let rates = [tax_rate("food", 200_000, true)];
+        assert_eq!(taxes(&rates, &[("food", 1200)]), vec![("food".to_string(), 1000, 200)]);
+        // 1000 / 1.19 = 840.34 and 840 * 0.19 = 159.6
+        let rates = [tax_rate("food", 190_000, true)];
+        assert_eq!(taxes(&rates, &[("food", 1000)]), vec![("food".to_string(), 840, 160)]);
+    }
+
+    #[test]
+    fn every_rate_of_a_category_applies_to_the_same_net_amount() {
+        let rates = [
+            tax_rate("food", 100_000, true),
+            tax_rate("food", 50_000, false),
+            tax_rate("food", 20_000, true),
+        ];
+        assert_eq!(
+            taxes(&rates, &[("food", 1120)]),
+            vec![
+                ("food".to_string(), 1000, 100),
+                ("food".to_string(), 1000, 50),
+                ("food".to_string(), 1000, 20),
+            ]
+        );
+    }
+
+    #[test]
+    fn taxes_go_by_category() {
+        let rates = [
+            tax_rate("books", 70_000, false),
+            tax_rate("food", 100_000, false),
+            tax_rate("toys", 200_000, false),
+        ];
+        assert_eq!(
+            taxes(&rates, &[("food", 1000), ("books", 500), ("untaxed", 300)]),
+            vec![("books".to_string(), 500, 35), ("food".to_string(), 1000, 100)]
+        );
+        assert!(taxes(&[], &[("food", 1000)]).is_empty());
+    }
+
+    #[test]
+    fn every_tax_rate_that_fits_makes_a_tax_line_that_fits() {
+        let amount = money(u64::MAX, &"X".repeat(MAX_CURRENCY_LEN), MAX_DECIMALS);
+        let mut largest = 0;
+        for name in 0..=MAX_TAX_LABEL_LEN {
+            for jurisdiction in 0..=MAX_TAX_LABEL_LEN {
+                let rate = TaxRate {
+                    name: "\u{1F9FE}".repeat(name),
+                    jurisdiction: "\u{00E9}".repeat(jurisdiction),
+                    category: "\u{20AC}".repeat(MAX_TAX_LABEL_LEN),
+                    rate: RATE_SCALE as u32,
+                    inclusive: true,
+                };
+                if vec![rate.clone()].validate().is_err() || ensure_fits(&rate).is_err() {
+                    continue;
+                }
+                let line = TaxLine {
+                    rate,
+                    taxable_amount: amount.clone(),
+                    tax: amount.clone(),
+                };
+                assert!(ensure_fits(&line).is_ok());
+                largest = largest.max(line.to_bytes().len());
+            }
+        }
+        // the rates at the limit were tried, not only small ones
+        assert!(largest > 320);
+    }
+
+    #[test]
+    fn a_full_status_history_keeps_the_first_change_and_fits() {
+        let code = "X".repeat(MAX_CURRENCY_LEN);
+        let amount = money(u64::MAX, &code, MAX_DECIMALS);
+        let currency = Currency {
+            code: code.clone(),
+            decimals: MAX_DECIMALS,
+        };
+        let principal = Principal::from_slice(&[0xff; 29]);
+        let mut header = OrderHeader {
+            id: OrderId::new(u64::MAX),
+            business_id: BusinessId::new(u64::MAX),
+            line_count: u32::MAX,
+            customer_id: Some(CustomerId::new(u64::MAX)),
+            unlinked_buyer: Some(principal),
+            discount: Some(DiscountLine {
+                coupon_id: CouponId::new(u64::MAX),
+                code: "C".repeat(MAX_COUPON_CODE_LEN),
+                discount: Discount::Fixed(amount.clone()),
+                amount: amount.clone(),
+            }),
+            subtotal: amount.clone(),
+            tax_line_count: u32::MAX,
+            total_price: amount.clone(),
+            exchange_rate: Some(ExchangeRate {
+                base: currency.clone(),
+                quote: currency,
+                rate: u64::MAX,
+                updated_by: principal,
+                updated_at: u64::MAX,
+            }),
+            status: OrderStatus::Pending,
+            status_history: Vec::new(),
+            created_at: u64::MAX,
+            updated_at: Some(u64::MAX),
+        };
+        for changed_at in 0..3 * MAX_STATUS_HISTORY as u64 {
+            record_status(
+                &mut header,
+                StatusChange {
+                    status: OrderStatus::PartiallyRefunded,
+                    changed_by: principal,
+                    changed_at: u64::MAX - changed_at,
+                },
+            );
+        }
+        let history = &header.status_history;
+        assert_eq!(history.len(), MAX_STATUS_HISTORY);
+        assert_eq!(history[0].changed_at, u64::MAX);
+        assert_eq!(history[MAX_STATUS_HISTORY - 1].changed_at, u64::MAX - 29);
+        assert!(ensure_fits(&header).is_ok());
+    }
+
+    #[test]
+    fn days_of_the_month_follow_leap_years() {
+        // 2000-02-29, 2000-03-01, 2024-02-29, 2024-03-01, 2100-02-28 and 2100-03-01
+        assert_eq!(day_of_month(11_016), 29);
+        assert_eq!(day_of_month(11_017), 1);
+        assert_eq!(day_of_month(19_782), 29);
+        assert_eq!(day_of_month(19_783), 1);
+        assert_eq!(day_of_month(47_540), 28);
+        assert_eq!(day_of_month(47_541), 1);
+        // 1970-01-01, 1970-01-31 and 2024-12-31
+        assert_eq!(day_of_month(0), 1);
+        assert_eq!(day_of_month(30), 31);
+        assert_eq!(day_of_month(20_088), 31);
+    }
+
+    #[test]
+    fn periods_start_on_mondays_and_the_first_of_the_month() {
+        // 2024-02-29 is a Thursday in the week of Monday 2024-02-26 and the month of 2024-02-01
+        assert_eq!(period_start(19_782, Granularity::Day), 19_782);
+        assert_eq!(period_start(19_782, Granularity::Week), 19_779);
+        assert_eq!(period_start(19_782, Granularity::Month), 19_754);
+        // a Monday starts its own week, and a Sunday belongs to the Monday before
+        assert_eq!(period_start(19_751, Granularity::Week), 19_751);
+        assert_eq!(period_start(19_757, Granularity::Week), 19_751);
+        // 2100-03-01 comes right after 2100-02-28
+        assert_eq!(period_start(47_541, Granularity::Month), 47_541);
+        assert_eq!(period_start(47_540, Granularity::Month), 47_513);
+        // the week before the epoch is cut short
+        assert_eq!(period_start(0, Granularity::Week), 0);
+        assert_eq!(period_start(2, Granularity::Week), 0);
+        assert_eq!(period_start(4, Granularity::Week), 4);
+        assert_eq!(period_start(30, Granularity::Month), 0);
+    }
+
+    #[test]
+    fn decodes_a_business_from_the_first_release() {
+        let old = BusinessV0 {
+            id: 4,
+            name: "Shop".to_string(),
+            description: "A shop".to_string(),
+            address: "Main St".to_string(),
+            created_at: 1,
+            updated_at: Some(2),
+        };
+        let business = Business::from_bytes(Cow::Owned(stored(0, &old)));
+        assert_eq!(business.id, BusinessId::new(4));
+        assert_eq!(business.owner, Principal::anonymous());
+        assert_eq!(business.name, "Shop");
+        assert_eq!(business.base_currency, Currency::legacy());
+        assert_eq!(business.updated_at, Some(2));
+    }
+
+    #[test]
+    fn decodes_older_business_layouts() {
+        let owner = Principal::management_canister();
+        let v1 = BusinessV1 {
+            id: 4,
+            owner,
+            name: "Shop".to_string(),
+            description: String::new(),
+            address: String::new(),
+            created_at: 1,
+            updated_at: None,
+        };
+        let business = Business::from_bytes(Cow::Owned(stored(1, &v1)));
+        assert_eq!((business.id, business.owner), (BusinessId::new(4), owner));
+
+        let v2 = BusinessV2 {
+            id: BusinessId::new(5),
+            owner,
+            name: "Shop".to_string(),
+            description: String::new(),
+            address: String::new(),
+            created_at: 1,
+            updated_at: None,
+        };
+        let business = Business::from_bytes(Cow::Owned(stored(2, &v2)));
+        assert_eq!(business.id, BusinessId::new(5));
+        assert_eq!(business.base_currency, Currency::legacy());
+    }
+
+    #[test]
+    fn decodes_a_product_from_the_first_release() {
+        let old = ProductV0 {
+            id: 9,
+            name: "Tea".to_string(),
+            description: String::new(),
+            price: 250,
+            created_at: 1,
+            updated_at: None,
+        };
+        let product = Product::from_bytes(Cow::Owned(stored(0, &old)));
+        assert_eq!(product.id, ProductId::new(9));
+        assert_eq!(product.business_id, BusinessId::new(0));
+        assert_eq!(product.price, usd(250));
+        assert_eq!(product.stock_quantity, 0);
+        assert!(product.prices.is_empty());
+        assert_eq!(product.tax_category, None);
+    }
+
+    #[test]
+    fn decodes_older_product_layouts() {
+        let v1 = ProductV1 {
+            id: 9,
+            business_id: 4,
+            name: "Tea".to_string(),
+            description: String::new(),
+            price: 250,
+            stock_quantity: 3,
+            created_at: 1,
+            updated_at: None,
+        };
+        let product = Product::from_bytes(Cow::Owned(stored(1, &v1)));
+        assert_eq!(product.business_id, BusinessId::new(4));
+        assert_eq!((product.price.clone(), product.stock_quantity), (usd(250), 3));
+
+        let v2 = ProductV2 {
+            id: ProductId::new(9),
+            business_id: BusinessId::new(4),
+            name: "Tea".to_string(),
+            description: String::new(),
+            price: 250,
+            stock_quantity: 3,
+            created_at: 1,
+            updated_at: None,
+        };
+        assert_eq!(Product::from_bytes(Cow::Owned(stored(2, &v2))).price, usd(250));
+
+        let v3 = ProductV3 {
+            id: ProductId::new(9),
+            business_id: BusinessId::new(4),
+            name: "Tea".to_string(),
+            description: String::new(),
+            price: usd(250),
+            stock_quantity: 3,
+            created_at: 1,
+            updated_at: None,
+        };
+        assert!(Product::from_bytes(Cow::Owned(stored(3, &v3))).prices.is_empty());
+
+        let v4 = ProductV4 {
+            id: ProductId::new(9),
+            business_id: BusinessId::new(4),
+            name: "Tea".to_string(),
+            description: String::new(),
+            price: usd(250),
+            prices: vec![Money {
+                amount: 230,
+                currency: "EUR".to_string(),
+                decimals: 2,
+            }],
+            stock_quantity: 3,
+            created_at: 1,
+            updated_at: None,
+        };
+        let product = Product::from_bytes(Cow::Owned(stored(4, &v4)));
+        assert_eq!(product.prices.len(), 1);
+        assert_eq!(product.tax_category, None);
+    }
+
+    #[test]
+    fn decodes_an_order_from_the_first_release() {
+        let tea = |price| ProductV0 {
+            id: 9,
+            name: "Tea".to_string(),
+            description: String::new(),
+            price,
+            created_at: 1,
+            updated_at: None,
+        };
+        let old = LegacyOrderV0 {
+            id: 12,
+            products: vec![tea(250), tea(250), tea(300)],
+            total_price: 800,
+            created_at: 1,
+            updated_at: None,
+        };
+        let order = Order::from(LegacyOrder::from_bytes(Cow::Owned(stored(0, &old))));
+        assert_eq!(order.id, OrderId::new(12));
+        assert_eq!(order.business_id, BusinessId::new(0));
+        assert_eq!(order.status, OrderStatus::Pending);
+        assert_eq!(order.total_price, usd(800));
+        let lines: Vec<(u32, u64, u64)> = order
+            .lines
+            .iter()
+            .map(|line| (line.quantity, line.unit_price.amount, line.line_total.amount))
+            .collect();
+        assert_eq!(lines, vec![(2, 250, 500), (1, 300, 300)]);
+    }
+
+    #[test]
+    fn decodes_older_order_header_layouts() {
+        let v1 = OrderHeaderV1 {
+            id: 12,
+            business_id: 4,
+            line_count: 2,
+            total_price: 800,
+            status: OrderStatus::Confirmed,
+            status_history: history(),
+            created_at: 1,
+            updated_at: None,
+        };
+        let header = OrderHeader::from_bytes(Cow::Owned(stored(1, &v1)));
+        assert_eq!((header.id, header.business_id), (OrderId::new(12), BusinessId::new(4)));
+        assert_eq!(header.subtotal, usd(800));
+        assert_eq!(header.total_price, usd(800));
+        assert_eq!((header.line_count, header.tax_line_count), (2, 0));
+        assert_eq!(header.status_history.len(), 1);
+
+        let v2 = OrderHeaderV2 {
+            id: OrderId::new(12),
+            business_id: BusinessId::new(4),
+            line_count: 2,
+            total_price: 800,
+            status: OrderStatus::Confirmed,
+            status_history: history(),
+            created_at: 1,
+            updated_at: None,
+        };
+        assert_eq!(OrderHeader::from_bytes(Cow::Owned(stored(2, &v2))).total_price, usd(800));
+
+        let v3 = OrderHeaderV3 {
+            id: OrderId::new(12),
+            business_id: BusinessId::new(4),
+            line_count: 2,
+            total_price: usd(800),
+            status: OrderStatus::Confirmed,
+            status_history: history(),
+            created_at: 1,
+            updated_at: None,
+        };
+        let header = OrderHeader::from_bytes(Cow::Owned(stored(3, &v3)));
+        assert!(header.exchange_rate.is_none());
+
+        let v4 = OrderHeaderV4 {
+            id: OrderId::new(12),
+            business_id: BusinessId::new(4),
+            line_count: 2,
+            total_price: usd(800),
+            exchange_rate: None,
+            status: OrderStatus::Confirmed,
+            status_history: history(),
+            created_at: 1,
+            updated_at: None,
+        };
+        let header = OrderHeader::from_bytes(Cow::Owned(stored(4, &v4)));
+        assert_eq!((header.subtotal, header.tax_line_count), (usd(800), 0));
+
+        let v5 = OrderHeaderV5 {
+            id: OrderId::new(12),
+            business_id: BusinessId::new(4),
+            line_count: 2,
+            subtotal: usd(700),
+            tax_line_count: 1,
+            total_price: usd(800),
+            exchange_rate: None,
+            status: OrderStatus::Confirmed,
+            status_history: history(),
+            created_at: 1,
+            updated_at: None,
+        };
+        let header = OrderHeader::from_bytes(Cow::Owned(stored(5, &v5)));
+        assert_eq!((header.subtotal, header.tax_line_count), (usd(700), 1));
+        assert!(header.discount.is_none() && header.customer_id.is_none());
+
+        let v6 = OrderHeaderV6 {
+            id: OrderId::new(12),
+            business_id: BusinessId::new(4),
+            line_count: 2,
+            customer: Some(Principal::anonymous()),
+            discount: None,
+            subtotal: usd(700),
+            tax_line_count: 1,
+            total_price: usd(800),
+            exchange_rate: None,
+            status: OrderStatus::Confirmed,
+            status_history: history(),
+            created_at: 1,
+            updated_at: None,
+        };
+        let header = OrderHeader::from_bytes(Cow::Owned(stored(6, &v6)));
+        assert_eq!((header.id, header.total_price), (OrderId::new(12), usd(800)));
+        // the buyer waits for `migrate_records` to link them to a customer
+        assert!(header.customer_id.is_none());
+        assert_eq!(header.unlinked_buyer, Some(Principal::anonymous()));
+
+        let v7 = OrderHeaderV7 {
+            id: OrderId::new(13),
+            business_id: BusinessId::new(4),
+            line_count: 2,
+            customer_id: Some(CustomerId::new(3)),
+            discount: None,
+            subtotal: usd(700),
+            tax_line_count: 1,
+            total_price: usd(800),
+            exchange_rate: None,
+            status: OrderStatus::Confirmed,
+            status_history: history(),
+            created_at: 1,
+            updated_at: None,
+        };
+        let header = OrderHeader::from_bytes(Cow::Owned(stored(7, &v7)));
+        assert_eq!(header.customer_id, Some(CustomerId::new(3)));
+        assert!(header.unlinked_buyer.is_none());
+    }
+
+    #[test]
+    fn decodes_older_order_line_layouts() {
+        let v1 = OrderLineV1 {
+            product_id: 9,
+            quantity: 2,
+            unit_price: 250,
+            line_total: 500,
+        };
+        let line = OrderLine::from_bytes(Cow::Owned(stored(1, &v1)));
+        assert_eq!(line.product_id, ProductId::new(9));
+        assert_eq!((line.unit_price, line.line_total), (usd(250), usd(500)));
+
+        let v2 = OrderLineV2 {
+            product_id: ProductId::new(9),
+            quantity: 2,
+            unit_price: 250,
+            line_total: 500,
+        };
+        assert_eq!(OrderLine::from_bytes(Cow::Owned(stored(2, &v2))).line_total, usd(500));
+    }
+
+    #[test]
+    fn decodes_older_member_and_payment_layouts() {
+        let v1 = MemberV1 {
+            business_id: 4,
+            principal: Principal::anonymous(),
+            role: Role::Cashier,
+            granted_at: 1,
+        };
+        let member = Member::from_bytes(Cow::Owned(stored(1, &v1)));
+        assert_eq!((member.business_id, member.role), (BusinessId::new(4), Role::Cashier));
+
+        let account = Account {
+            owner: Principal::anonymous(),
+            subaccount: None,
+        };
+        let v1 = PaymentV1 {
+            ledger: Principal::management_canister(),
+            from: account.clone(),
+            to: account,
+            amount: usd(800),
+            block_index: Nat::from(3u64),
+            paid_at: 1,
+        };
+        let payment = Payment::from_bytes(Cow::Owned(stored(1, &v1)));
+        assert_eq!(payment.method, PaymentMethod::Approval);
+        assert_eq!((payment.amount, payment.overpaid), (usd(800), usd(0)));
+    }
+
+    #[test]
+    fn only_the_first_release_kinds_decode_without_an_envelope() {
+        let bytes = Encode!(&MemberV1 {
+            business_id: 4,
+            principal: Principal::anonymous(),
+            role: Role::Cashier,
+            granted_at: 1,
+        })
+        .unwrap();
+        assert!(Member::decode_legacy(0, &bytes).is_err());
+        assert!(OrderHeader::decode_legacy(0, &bytes).is_err());
+        assert!(Customer::decode_legacy(0, &bytes).is_err());
+    }
+
+    #[test]
+    fn current_layouts_round_trip() {
+        let product = Product {
+            id: ProductId::new(9),
+            business_id: BusinessId::new(4),
+            name: "Tea".to_string(),
+            price: usd(250),
+            tax_category: Some("food".to_string()),
+            stock_quantity: 3,
+            ..Default::default()
+        };
+        let decoded = Product::from_bytes(product.to_bytes());
+        assert_eq!(decoded.id, product.id);
+        assert_eq!(decoded.tax_category, product.tax_category);
+        assert_eq!(product.to_bytes()[..2], [ENVELOPE_TAG, Product::VERSION]);
+    }
+}
@@ -0,0 +1 @@
+// The tests in `tests/` run the backend against `mock_icrc_ledger` on PocketIC; see the README.
@@ -0,0 +1,541 @@
+// Runs the backend against `mock_icrc_ledger` on PocketIC. Both canisters have to be built for
+// wasm32 first and POCKET_IC_BIN has to point at the PocketIC server; see the README.
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use candid::{encode_one, CandidType, Deserialize, Nat, Principal};
+use pocket_ic::common::rest::RawEffectivePrincipal;
+use pocket_ic::{call_candid_as, query_candid_as, PocketIc};
+use std::path::Path;
+
+const FEE: u64 = 10_000;
+const PRICE: u64 = 100_000_000;
+
+// the backend's and the ledger's candid types, cut down to the fields the tests use
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+struct BusinessId {
+    business: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+struct ProductId {
+    product: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+struct OrderId {
+    order: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+struct CustomerId {
+    customer: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+struct ReturnId {
+    rma: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+struct Currency {
+    code: String,
+    decimals: u8,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+struct Money {
+    currency: String,
+    decimals: u8,
+    amount: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+struct Account {
+    owner: Principal,
+    subaccount: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+enum Error {
+    Internal { msg: String },
+    InvalidInput { msg: String },
+    PaymentFailed { msg: String },
+    NotFound { msg: String },
+    InsufficientStock { msg: String },
+    Unauthorized { msg: String },
+    CurrencyMismatch { msg: String },
+    LimitExceeded { msg: String },
+    InvalidState { msg: String },
+    Conflict { msg: String },
+}
+
+#[derive(CandidType, Deserialize)]
+struct BusinessPayload {
+    name: String,
+    description: String,
+    address: String,
+    base_currency: Currency,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct Business {
+    id: BusinessId,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ProductPayload {
+    business_id: BusinessId,
+    name: String,
+    description: String,
+    price: Money,
+    prices: Vec<Money>,
+    tax_category: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct Product {
+    id: ProductId,
+    stock_quantity: u64,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct LedgerConfig {
+    ledger: Principal,
+}
+
+#[derive(CandidType, Deserialize)]
+struct OrderLinePayload {
+    product_id: ProductId,
+    quantity: u32,
+}
+
+#[derive(CandidType, Deserialize)]
+struct OrderPayload {
+    business_id: BusinessId,
+    lines: Vec<OrderLinePayload>,
+    customer_id: Option<CustomerId>,
+    currency: Option<String>,
+    coupon_code: Option<String>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+enum OrderStatus {
+    Pending,
+    Confirmed,
+    Paid,
+    Shipped,
+    Delivered,
+    Cancelled,
+    PartiallyRefunded,
+    Refunded,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+enum PaymentMethod {
+    Approval,
+    Deposit,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct Payment {
+    method: PaymentMethod,
+    from: Account,
+    amount: Money,
+    overpaid: Money,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct Order {
+    id: OrderId,
+    status: OrderStatus,
+    total_price: Money,
+    payment: Option<Payment>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ReturnLinePayload {
+    line_no: u32,
+    quantity: u32,
+    reason: String,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ReturnPayload {
+    order_id: OrderId,
+    lines: Vec<ReturnLinePayload>,
+    refund_to: Option<Account>,
+    refund_amount: Option<Money>,
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+enum ReturnStatus {
+    Requested,
+    Approved,
+    Rejected,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct Refund {
+    to: Account,
+    amount: Money,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+struct ReturnAuthorization {
+    id: ReturnId,
+    status: ReturnStatus,
+    refund_amount: Money,
+    refund: Option<Refund>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct InitArgs {
+    symbol: String,
+    decimals: u8,
+    fee: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ApproveArgs {
+    from_subaccount: Option<Vec<u8>>,
+    spender: Account,
+    amount: Nat,
+    expected_allowance: Option<Nat>,
+    expires_at: Option<u64>,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize, Debug)]
+enum ApproveError {
+    BadFee { expected_fee: Nat },
+    InsufficientFunds { balance: Nat },
+    AllowanceChanged { current_allowance: Nat },
+    Expired { ledger_time: u64 },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+struct Env {
+    pic: PocketIc,
+    backend: Principal,
+    ledger: Principal,
+    owner: Principal,
+    buyer: Principal,
+    business_id: BusinessId,
+    product_id: ProductId,
+}
+
+fn wasm(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../target/wasm32-unknown-unknown/release")
+        .join(format!("{}.wasm", name));
+    std::fs::read(&path).unwrap_or_else(|e| {
+        panic!(
+            "couldn't read {}: {}. build it with \
+             `cargo build --target wasm32-unknown-unknown --release -p {}`",
+            path.display(),
+            e,
+            name
+        )
+    })
+}
+
+fn money(amount: u64) -> Money {
+    Money {
+        currency: "MOCK".to_string(),
+        decimals: 8,
+        amount,
+    }
+}
+
+fn account(owner: Principal) -> Account {
+    Account {
+        owner,
+        subaccount: None,
+    }
+}
+
+impl Env {
+    // a business selling one product priced at PRICE in the ledger's currency, with 10 in stock
+    fn new() -> Self {
+        let pic = PocketIc::new();
+        let owner = Principal::from_slice(&[1; 29]);
+        let buyer = Principal::from_slice(&[2; 29]);
+
+        let ledger = pic.create_canister_with_settings(Some(owner), None);
+        pic.add_cycles(ledger, 2_000_000_000_000);
+        let init = Some(InitArgs {
+            symbol: "MOCK".to_string(),
+            decimals: 8,
+            fee: Nat::from(FEE),
+        });
+        pic.install_canister(
+            ledger,
+            wasm("mock_icrc_ledger"),
+            encode_one(init).unwrap(),
+            Some(owner),
+        );
+        let backend = pic.create_canister_with_settings(Some(owner), None);
+        pic.add_cycles(backend, 2_000_000_000_000);
+        pic.install_canister(
+            backend,
+            wasm("icp_rust_boilerplate_backend"),
+            candid::encode_args(()).unwrap(),
+            Some(owner),
+        );
+
+        let mut env = Env {
+            pic,
+            backend,
+            ledger,
+            owner,
+            buyer,
+            business_id: BusinessId { business: 0 },
+            product_id: ProductId { product: 0 },
+        };
+        let currency = Currency {
+            code: "MOCK".to_string(),
+            decimals: 8,
+        };
+        let _: LedgerConfig = env.backend_call(owner, "set_ledger", (currency.clone(), ledger));
+        let business: Business = env.backend_call(
+            owner,
+            "add_business",
+            (BusinessPayload {
+                name: "Mock shop".to_string(),
+                description: "sells things for mock tokens".to_string(),
+                address: "1 Test Street".to_string(),
+                base_currency: currency,
+            },),
+        );
+        env.business_id = business.id;
+        let product: Product = env.backend_call(
+            owner,
+            "add_product",
+            (ProductPayload {
+                business_id: business.id,
+                name: "Widget".to_string(),
+                description: "a widget".to_string(),
+                price: money(PRICE),
+                prices: Vec::new(),
+                tax_category: None,
+            },),
+        );
+        let product: Product = env.backend_call(owner, "restock_product", (product.id, 10u64));
+        env.product_id = product.id;
+        env
+    }
+
+    fn call<I: ArgumentEncoder, O: for<'a> ArgumentDecoder<'a>>(
+        &self,
+        canister: Principal,
+        sender: Principal,
+        method: &str,
+        args: I,
+    ) -> O {
+        call_candid_as(&self.pic, canister, RawEffectivePrincipal::None, sender, method, args)
+            .unwrap_or_else(|e| panic!("{} failed: {:?}", method, e))
+    }
+
+    fn backend_try<I: ArgumentEncoder, T: CandidType + for<'a> Deserialize<'a>>(
+        &self,
+        sender: Principal,
+        method: &str,
+        args: I,
+    ) -> Result<T, Error> {
+        let (result,): (Result<T, Error>,) = self.call(self.backend, sender, method, args);
+        result
+    }
+
+    fn backend_call<I: ArgumentEncoder, T: CandidType + for<'a> Deserialize<'a>>(
+        &self,
+        sender: Principal,
+        method: &str,
+        args: I,
+    ) -> T {
+        self.backend_try(sender, method, args)
+            .unwrap_or_else(|e| panic!("{} returned {:?}", method, e))
+    }
+
+    fn get_order(&self, id: OrderId) -> Order {
+        let (result,): (Result<Order, Error>,) =
+            query_candid_as(&self.pic, self.backend, self.owner, "get_order", (id,))
+                .unwrap_or_else(|e| panic!("get_order failed: {:?}", e));
+        result.unwrap_or_else(|e| panic!("get_order returned {:?}", e))
+    }
+
+    fn mint(&self, to: Account, amount: u64) {
+        let _: (Nat,) = self.call(self.ledger, self.owner, "mint", (to, Nat::from(amount)));
+    }
+
+    fn balance(&self, of: Account) -> u64 {
+        let (balance,): (Nat,) =
+            query_candid_as(&self.pic, self.ledger, self.owner, "icrc1_balance_of", (of,))
+                .unwrap_or_else(|e| panic!("icrc1_balance_of failed: {:?}", e));
+        u64::try_from(balance.0).unwrap()
+    }
+
+    fn approve(&self, owner: Principal, amount: u64) {
+        let (result,): (Result<Nat, ApproveError>,) = self.call(
+            self.ledger,
+            owner,
+            "icrc2_approve",
+            (ApproveArgs {
+                from_subaccount: None,
+                spender: account(self.backend),
+                amount: Nat::from(amount),
+                expected_allowance: None,
+                expires_at: None,
+                fee: None,
+                memo: None,
+                created_at_time: None,
+            },),
+        );
+        result.unwrap_or_else(|e| panic!("icrc2_approve returned {:?}", e));
+    }
+
+    fn business_account(&self) -> Account {
+        self.backend_call(self.owner, "get_business_account", (self.business_id,))
+    }
+
+    // a confirmed order for `quantity` units of the product
+    fn confirmed_order(&self, quantity: u32) -> Order {
+        let order: Order = self.backend_call(
+            self.owner,
+            "create_order",
+            (OrderPayload {
+                business_id: self.business_id,
+                lines: vec![OrderLinePayload {
+                    product_id: self.product_id,
+                    quantity,
+                }],
+                customer_id: None,
+                currency: None,
+                coupon_code: None,
+            },),
+        );
+        self.backend_call(self.owner, "confirm_order", (order.id,))
+    }
+}
+
+#[test]
+#[ignore = "needs the PocketIC server and the canister wasm; see the README"]
+fn pay_order_pulls_the_total_into_the_business_account() {
+    let env = Env::new();
+    env.mint(account(env.buyer), 10 * PRICE);
+    let order = env.confirmed_order(2);
+    assert_eq!(order.total_price, money(2 * PRICE));
+    env.approve(env.buyer, 2 * PRICE + FEE);
+
+    let paid: Order = env.backend_call(env.buyer, "pay_order", (order.id,));
+
+    assert_eq!(paid.status, OrderStatus::Paid);
+    let payment = paid.payment.expect("a paid order has a payment");
+    assert_eq!(payment.method, PaymentMethod::Approval);
+    assert_eq!(payment.from, account(env.buyer));
+    assert_eq!(payment.amount, money(2 * PRICE));
+    assert_eq!(payment.overpaid, money(0));
+    assert_eq!(env.balance(env.business_account()), 2 * PRICE);
+    // one fee for the approval and one for the transfer
+    assert_eq!(env.balance(account(env.buyer)), 8 * PRICE - 2 * FEE);
+}
+
+#[test]
+#[ignore = "needs the PocketIC server and the canister wasm; see the README"]
+fn pay_order_leaves_the_order_confirmed_when_the_allowance_is_short() {
+    let env = Env::new();
+    env.mint(account(env.buyer), 10 * PRICE);
+    let order = env.confirmed_order(1);
+    // the allowance has to cover the ledger fee as well
+    env.approve(env.buyer, PRICE);
+
+    let result: Result<Order, Error> = env.backend_try(env.buyer, "pay_order", (order.id,));
+
+    assert!(matches!(result, Err(Error::PaymentFailed { .. })), "{:?}", result);
+    let order = env.get_order(order.id);
+    assert_eq!(order.status, OrderStatus::Confirmed);
+    assert!(order.payment.is_none());
+    assert_eq!(env.balance(env.business_account()), 0);
+    // the order can still be paid once the allowance is raised
+    env.approve(env.buyer, PRICE + FEE);
+    let paid: Order = env.backend_call(env.buyer, "pay_order", (order.id,));
+    assert_eq!(paid.status, OrderStatus::Paid);
+}
+
+#[test]
+#[ignore = "needs the PocketIC server and the canister wasm; see the README"]
+fn check_order_payment_sweeps_a_deposit_and_records_the_overpayment() {
+    let env = Env::new();
+    let order = env.confirmed_order(1);
+    let deposit: Account =
+        env.backend_call(env.buyer, "get_order_deposit_account", (order.id,));
+
+    // short of the total plus one fee, so nothing happens yet
+    env.mint(deposit.clone(), PRICE);
+    let unpaid: Order = env.backend_call(env.buyer, "check_order_payment", (order.id,));
+    assert_eq!(unpaid.status, OrderStatus::Confirmed);
+    assert_eq!(env.balance(deposit.clone()), PRICE);
+
+    env.mint(deposit.clone(), FEE + 500);
+    let paid: Order = env.backend_call(env.buyer, "check_order_payment", (order.id,));
+
+    assert_eq!(paid.status, OrderStatus::Paid);
+    let payment = paid.payment.expect("a paid order has a payment");
+    assert_eq!(payment.method, PaymentMethod::Deposit);
+    assert_eq!(payment.amount, money(PRICE));
+    assert_eq!(payment.overpaid, money(500));
+    assert_eq!(env.balance(deposit.clone()), 0);
+    assert_eq!(env.balance(env.business_account()), PRICE + 500);
+
+    // funds that arrive later are swept and added to the overpayment
+    env.mint(deposit.clone(), FEE + 700);
+    let order: Order = env.backend_call(env.buyer, "check_order_payment", (order.id,));
+    assert_eq!(order.status, OrderStatus::Paid);
+    assert_eq!(order.payment.expect("still paid").overpaid, money(1_200));
+    assert_eq!(env.balance(env.business_account()), PRICE + 1_200);
+}
+
+#[test]
+#[ignore = "needs the PocketIC server and the canister wasm; see the README"]
+fn approve_return_refunds_the_buyer_on_the_ledger() {
+    let env = Env::new();
+    env.mint(account(env.buyer), 10 * PRICE);
+    let order = env.confirmed_order(2);
+    env.approve(env.buyer, 2 * PRICE + FEE);
+    let _: Order = env.backend_call(env.buyer, "pay_order", (order.id,));
+    let before = env.balance(account(env.buyer));
+
+    let rma: ReturnAuthorization = env.backend_call(
+        env.owner,
+        "request_return",
+        (ReturnPayload {
+            order_id: order.id,
+            lines: vec![ReturnLinePayload {
+                line_no: 0,
+                quantity: 1,
+                reason: "arrived broken".to_string(),
+            }],
+            refund_to: None,
+            refund_amount: None,
+        },),
+    );
+    assert_eq!(rma.refund_amount, money(PRICE));
+    let approved: ReturnAuthorization =
+        env.backend_call(env.owner, "approve_return", (rma.id, true));
+
+    assert_eq!(approved.status, ReturnStatus::Approved);
+    let refund = approved.refund.expect("a ledger-paid order is refunded on the ledger");
+    assert_eq!(refund.to, account(env.buyer));
+    assert_eq!(refund.amount, money(PRICE));
+    assert_eq!(env.balance(account(env.buyer)), before + PRICE);
+    // the ledger fee comes out of the business's account
+    assert_eq!(env.balance(env.business_account()), PRICE - FEE);
+    assert_eq!(env.get_order(order.id).status, OrderStatus::PartiallyRefunded);
+    let product: Product = env.backend_call(env.owner, "get_product", (env.product_id,));
+    assert_eq!(product.stock_quantity, 9);
+}
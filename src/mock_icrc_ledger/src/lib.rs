@@ -0,0 +1,333 @@
+// A minimal ICRC-1/ICRC-2 ledger for local testing of the payment endpoints. Balances and
+// allowances live on the heap, so they are lost on upgrade; `mint` lets anyone create tokens.
+use candid::{CandidType, Deserialize, Nat, Principal};
+use ic_cdk::api::{caller, time};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+const DEFAULT_SUBACCOUNT: [u8; 32] = [0; 32];
+
+#[derive(CandidType, Clone, Debug, Deserialize)]
+struct Account {
+    owner: Principal,
+    subaccount: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct InitArgs {
+    symbol: String,
+    decimals: u8,
+    fee: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+struct TransferArg {
+    from_subaccount: Option<Vec<u8>>,
+    to: Account,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+enum TransferError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+struct ApproveArgs {
+    from_subaccount: Option<Vec<u8>>,
+    spender: Account,
+    amount: Nat,
+    expected_allowance: Option<Nat>,
+    expires_at: Option<u64>,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+enum ApproveError {
+    BadFee { expected_fee: Nat },
+    InsufficientFunds { balance: Nat },
+    AllowanceChanged { current_allowance: Nat },
+    Expired { ledger_time: u64 },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+#[derive(CandidType, Deserialize)]
+struct AllowanceArgs {
+    account: Account,
+    spender: Account,
+}
+
+#[derive(CandidType, Deserialize)]
+struct Allowance {
+    allowance: Nat,
+    expires_at: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct TransferFromArgs {
+    spender_subaccount: Option<Vec<u8>>,
+    from: Account,
+    to: Account,
+    amount: Nat,
+    fee: Option<Nat>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+enum TransferFromError {
+    BadFee { expected_fee: Nat },
+    BadBurn { min_burn_amount: Nat },
+    InsufficientFunds { balance: Nat },
+    InsufficientAllowance { allowance: Nat },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: Nat },
+    TemporarilyUnavailable,
+    GenericError { error_code: Nat, message: String },
+}
+
+// accounts with no subaccount and with the all-zero one are the same account
+type AccountKey = (Principal, Vec<u8>);
+
+fn key(owner: Principal, subaccount: &Option<Vec<u8>>) -> AccountKey {
+    (
+        owner,
+        subaccount.clone().unwrap_or_else(|| DEFAULT_SUBACCOUNT.to_vec()),
+    )
+}
+
+struct State {
+    symbol: String,
+    decimals: u8,
+    fee: u128,
+    balances: BTreeMap<AccountKey, u128>,
+    // (account, spender) pairs
+    allowances: BTreeMap<(AccountKey, AccountKey), (u128, Option<u64>)>,
+    next_block: u128,
+}
+
+impl State {
+    fn balance(&self, account: &AccountKey) -> u128 {
+        self.balances.get(account).copied().unwrap_or(0)
+    }
+
+    // expired allowances count as none
+    fn allowance(&self, account: &AccountKey, spender: &AccountKey) -> (u128, Option<u64>) {
+        match self.allowances.get(&(account.clone(), spender.clone())) {
+            Some((_, Some(expires_at))) if *expires_at <= time() => (0, None),
+            Some(allowance) => *allowance,
+            None => (0, None),
+        }
+    }
+
+    // moves `amount` and burns `fee`; the caller has checked the balance
+    fn transfer(&mut self, from: &AccountKey, to: &AccountKey, amount: u128, fee: u128) -> Nat {
+        let from_balance = self.balance(from);
+        self.balances.insert(from.clone(), from_balance - amount - fee);
+        let to_balance = self.balance(to);
+        self.balances.insert(to.clone(), to_balance + amount);
+        self.block()
+    }
+
+    fn block(&mut self) -> Nat {
+        let block = self.next_block;
+        self.next_block += 1;
+        Nat::from(block)
+    }
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State {
+        symbol: "MOCK".to_string(),
+        decimals: 8,
+        fee: 10_000,
+        balances: BTreeMap::new(),
+        allowances: BTreeMap::new(),
+        next_block: 0,
+    });
+}
+
+fn to_u128(value: &Nat) -> Option<u128> {
+    u128::try_from(&value.0).ok()
+}
+
+#[ic_cdk::init]
+fn init(args: Option<InitArgs>) {
+    if let Some(args) = args {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            state.symbol = args.symbol;
+            state.decimals = args.decimals;
+            state.fee = to_u128(&args.fee).unwrap_or_else(|| ic_cdk::trap("the fee is too large"));
+        });
+    }
+}
+
+#[ic_cdk::query]
+fn icrc1_symbol() -> String {
+    STATE.with(|state| state.borrow().symbol.clone())
+}
+
+#[ic_cdk::query]
+fn icrc1_decimals() -> u8 {
+    STATE.with(|state| state.borrow().decimals)
+}
+
+#[ic_cdk::query]
+fn icrc1_fee() -> Nat {
+    STATE.with(|state| Nat::from(state.borrow().fee))
+}
+
+#[ic_cdk::query]
+fn icrc1_balance_of(account: Account) -> Nat {
+    STATE.with(|state| Nat::from(state.borrow().balance(&key(account.owner, &account.subaccount))))
+}
+
+// creates tokens out of thin air; only a mock would let anyone do this
+#[ic_cdk::update]
+fn mint(to: Account, amount: Nat) -> Nat {
+    let amount = to_u128(&amount).unwrap_or_else(|| ic_cdk::trap("the amount is too large"));
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let to = key(to.owner, &to.subaccount);
+        let balance = state.balance(&to);
+        state.balances.insert(to, balance + amount);
+        state.block()
+    })
+}
+
+#[ic_cdk::update]
+fn icrc1_transfer(args: TransferArg) -> Result<Nat, TransferError> {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if args.fee.as_ref().is_some_and(|fee| to_u128(fee) != Some(state.fee)) {
+            return Err(TransferError::BadFee {
+                expected_fee: Nat::from(state.fee),
+            });
+        }
+        let from = key(caller(), &args.from_subaccount);
+        let to = key(args.to.owner, &args.to.subaccount);
+        let balance = state.balance(&from);
+        match to_u128(&args.amount) {
+            Some(amount) if amount + state.fee <= balance => {
+                let fee = state.fee;
+                Ok(state.transfer(&from, &to, amount, fee))
+            }
+            _ => Err(TransferError::InsufficientFunds {
+                balance: Nat::from(balance),
+            }),
+        }
+    })
+}
+
+#[ic_cdk::update]
+fn icrc2_approve(args: ApproveArgs) -> Result<Nat, ApproveError> {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if args.fee.as_ref().is_some_and(|fee| to_u128(fee) != Some(state.fee)) {
+            return Err(ApproveError::BadFee {
+                expected_fee: Nat::from(state.fee),
+            });
+        }
+        if args.expires_at.is_some_and(|expires_at| expires_at <= time()) {
+            return Err(ApproveError::Expired { ledger_time: time() });
+        }
+        let account = key(caller(), &args.from_subaccount);
+        let spender = key(args.spender.owner, &args.spender.subaccount);
+        let (current, _) = state.allowance(&account, &spender);
+        if args
+            .expected_allowance
+            .as_ref()
+            .is_some_and(|expected| to_u128(expected) != Some(current))
+        {
+            return Err(ApproveError::AllowanceChanged {
+                current_allowance: Nat::from(current),
+            });
+        }
+        let balance = state.balance(&account);
+        if balance < state.fee {
+            return Err(ApproveError::InsufficientFunds {
+                balance: Nat::from(balance),
+            });
+        }
+        let amount = to_u128(&args.amount).unwrap_or(u128::MAX);
+        let fee = state.fee;
+        state.balances.insert(account.clone(), balance - fee);
+        state
+            .allowances
+            .insert((account, spender), (amount, args.expires_at));
+        Ok(state.block())
+    })
+}
+
+#[ic_cdk::query]
+fn icrc2_allowance(args: AllowanceArgs) -> Allowance {
+    STATE.with(|state| {
+        let (allowance, expires_at) = state.borrow().allowance(
+            &key(args.account.owner, &args.account.subaccount),
+            &key(args.spender.owner, &args.spender.subaccount),
+        );
+        Allowance {
+            allowance: Nat::from(allowance),
+            expires_at,
+        }
+    })
+}
+
+// the allowance has to cover the amount plus the fee, as on the real ledger
+#[ic_cdk::update]
+fn icrc2_transfer_from(args: TransferFromArgs) -> Result<Nat, TransferFromError> {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if args.fee.as_ref().is_some_and(|fee| to_u128(fee) != Some(state.fee)) {
+            return Err(TransferFromError::BadFee {
+                expected_fee: Nat::from(state.fee),
+            });
+        }
+        let spender = key(caller(), &args.spender_subaccount);
+        let from = key(args.from.owner, &args.from.subaccount);
+        let to = key(args.to.owner, &args.to.subaccount);
+        let fee = state.fee;
+        let Some(amount) = to_u128(&args.amount) else {
+            return Err(TransferFromError::GenericError {
+                error_code: Nat::from(0u8),
+                message: "the amount is too large".to_string(),
+            });
+        };
+        let (allowance, expires_at) = state.allowance(&from, &spender);
+        if allowance < amount + fee {
+            return Err(TransferFromError::InsufficientAllowance {
+                allowance: Nat::from(allowance),
+            });
+        }
+        let balance = state.balance(&from);
+        if balance < amount + fee {
+            return Err(TransferFromError::InsufficientFunds {
+                balance: Nat::from(balance),
+            });
+        }
+        state
+            .allowances
+            .insert((from.clone(), spender), (allowance - amount - fee, expires_at));
+        Ok(state.transfer(&from, &to, amount, fee))
+    })
+}
+
+ic_cdk::export_candid!();